@@ -0,0 +1,141 @@
+/// Linguistic analysis (tokens, lemmas, part-of-speech and chunk tags) from [`nlprule`], to
+/// understand why rules do or do not apply.
+use serde::{Deserialize, Serialize};
+
+use crate::api;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Token {
+    pub text: String,
+    pub offset: usize,
+    pub length: usize,
+    pub lemmas: Vec<String>,
+    pub pos_tags: Vec<String>,
+    pub chunks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Sentence {
+    pub text: String,
+    pub offset: usize,
+    pub length: usize,
+    pub tokens: Vec<Token>,
+}
+
+/// Values without duplicates, in order of appearance
+fn unique<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    for v in values {
+        if !v.is_empty() && !out.iter().any(|o| o == v) {
+            out.push(v.into());
+        }
+    }
+    out
+}
+
+impl Sentence {
+    /// Analyze the sentences of the text contained in the annotations
+    pub fn analyze<'t>(
+        sentences: impl Iterator<Item = nlprule::types::Sentence<'t>>,
+        annotations: &api::Annotations,
+    ) -> Vec<Self> {
+        sentences
+            .map(|sentence| {
+                let span = sentence.span().char();
+                let (offset, end) = annotations.translate_span(span.start, span.end);
+                Sentence {
+                    text: sentence.text().to_string(),
+                    offset,
+                    length: end - offset,
+                    tokens: sentence
+                        .tokens()
+                        .iter()
+                        .filter(|t| !t.word().as_str().is_empty())
+                        .map(|t| {
+                            let span = t.span().char();
+                            let (offset, end) = annotations.translate_span(span.start, span.end);
+                            let tags = t.word().tags();
+                            Token {
+                                text: t.word().as_str().into(),
+                                offset,
+                                length: end - offset,
+                                lemmas: unique(tags.iter().map(|t| t.lemma().as_str())),
+                                pos_tags: unique(tags.iter().map(|t| t.pos().as_str())),
+                                chunks: t.chunks().to_vec(),
+                            }
+                        })
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+    /// Display the tokens as a table
+    pub fn table(&self) -> String {
+        let header = ["Token", "Span", "Lemmas", "POS", "Chunks"].map(String::from);
+        let rows: Vec<[String; 5]> = std::iter::once(header)
+            .chain(self.tokens.iter().map(|t| {
+                [
+                    t.text.clone(),
+                    format!("{}..{}", t.offset, t.offset + t.length),
+                    t.lemmas.join(" "),
+                    t.pos_tags.join(" "),
+                    t.chunks.join(" "),
+                ]
+            }))
+            .collect();
+        let widths: Vec<usize> = (0..5)
+            .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+            .collect();
+        rows.iter()
+            .map(|r| {
+                r.iter()
+                    .zip(&widths)
+                    .map(|(c, w)| format!("{:w$}", c, w = w))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn table() {
+        let sentence = Sentence {
+            text: "Hi there".into(),
+            offset: 0,
+            length: 8,
+            tokens: vec![
+                Token {
+                    text: "Hi".into(),
+                    offset: 0,
+                    length: 2,
+                    lemmas: vec!["hi".into()],
+                    pos_tags: vec!["UH".into()],
+                    chunks: vec![],
+                },
+                Token {
+                    text: "there".into(),
+                    offset: 3,
+                    length: 5,
+                    lemmas: vec!["there".into()],
+                    pos_tags: vec!["EX".into(), "RB".into()],
+                    chunks: vec!["B-ADVP".into()],
+                },
+            ],
+        };
+        assert_eq!(
+            sentence.table(),
+            "Token  Span  Lemmas  POS    Chunks\n\
+             Hi     0..2  hi      UH\n\
+             there  3..8  there   EX RB  B-ADVP"
+        );
+    }
+}
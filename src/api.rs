@@ -4,6 +4,8 @@
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::formats::Format;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Language {
     name: String,
@@ -46,9 +48,89 @@ mod test {
         let l = Language::from_code("EN_US");
         assert_eq!(l, Language::default());
     }
+    #[test]
+    fn translate_span() {
+        let mut annotations = Annotations::default();
+        annotations.push_text("Hé ");
+        annotations.push_markup("<b>", None);
+        annotations.push_text("bold");
+        annotations.push_markup("</b>", None);
+        annotations.push_text(" ");
+        annotations.push_markup("<code>x</code>", Some("X"));
+        assert_eq!(annotations.text(), "Hé bold X");
+        assert_eq!(annotations.source(), "Hé <b>bold</b> <code>x</code>");
+        // "Hé"
+        assert_eq!(annotations.translate_span(0, 2), (0, 2));
+        // "bold"
+        assert_eq!(annotations.translate_span(3, 7), (6, 10));
+        // "X"
+        assert_eq!(annotations.translate_span(8, 9), (15, 29));
+        // "bold X"
+        assert_eq!(annotations.translate_span(3, 9), (6, 29));
+        // Empty span at the end
+        assert_eq!(annotations.translate_span(9, 9), (29, 29));
+    }
+    #[test]
+    fn split() {
+        let mut annotations = Annotations::default();
+        annotations.push_text("First paragraph.\n\nA sentence. ");
+        annotations.push_markup("<code>x</code>", Some("X"));
+        annotations.push_text(" and é more words");
+        let chunks = annotations.split(20);
+        let texts: Vec<_> = chunks.iter().map(|(_, c)| c.text()).collect();
+        assert_eq!(
+            texts,
+            ["First paragraph.\n\n", "A sentence. ", "X and é more words"]
+        );
+        let source = annotations.source();
+        let source_chars: Vec<char> = source.chars().collect();
+        for (offset, chunk) in &chunks {
+            let chunk_source = chunk.source();
+            let len = chunk_source.chars().count();
+            assert_eq!(
+                source_chars[*offset..offset + len]
+                    .iter()
+                    .collect::<String>(),
+                chunk_source
+            );
+        }
+        // Words, or anywhere for long words
+        let texts: Vec<_> = annotations
+            .split(8)
+            .into_iter()
+            .map(|(_, c)| c.text())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "First ",
+                "paragrap",
+                "h.\n\n",
+                "A ",
+                "sentence",
+                ". ",
+                "X and é ",
+                "more ",
+                "words"
+            ]
+        );
+        assert_eq!(annotations.split(100), [(0, annotations.clone())]);
+    }
+    #[test]
+    fn rule_urls() {
+        // As in the LanguageTool API
+        let rule = Rule::from_id("A/B".into()).with_url(Some("https://example.com"));
+        let json = serde_json::to_value(&rule).unwrap();
+        assert_eq!(
+            json["urls"],
+            serde_json::json!([{ "value": "https://example.com" }])
+        );
+        let rule: Rule = serde_json::from_value(json).unwrap();
+        assert_eq!(rule.urls().collect::<Vec<_>>(), ["https://example.com"]);
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 #[serde(rename_all = "camelCase")]
 pub enum AnnotationElement {
@@ -59,6 +141,12 @@ pub enum AnnotationElement {
         markup: String,
         // Interpret the markup as this string for analysis.
         // E.g. "\n\n" when `markup` is `<p>`
+        #[serde(
+            rename = "interpretAs",
+            alias = "interpret_as",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
         interpret_as: Option<String>,
     },
 }
@@ -76,9 +164,16 @@ impl AnnotationElement {
             AnnotationElement::Markup { interpret_as, .. } => interpret_as.as_deref().unwrap_or(""),
         }
     }
+    /// The element in the original document
+    pub fn source(&self) -> &str {
+        match self {
+            AnnotationElement::Text { text } => text,
+            AnnotationElement::Markup { markup, .. } => markup,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Annotations {
     pub annotation: Vec<AnnotationElement>,
 }
@@ -103,26 +198,65 @@ impl Annotations {
             .unwrap_or_default()
     }
 
-    /// Obtains the length of the text contained in the annotations
+    /// Obtains the length (in characters) of the text contained in the annotations
     pub fn text_len(&self) -> usize {
         self.annotation
             .iter()
-            .map(|v| match v {
-                AnnotationElement::Text { text } => text.len(),
-                // All whitespace markup should really be handled as what it is to preserve context...
-                AnnotationElement::Markup { markup, .. }
-                    if !markup.is_empty() && markup.trim().is_empty() =>
-                {
-                    markup.len()
-                }
-                AnnotationElement::Markup { interpret_as, .. } => {
-                    interpret_as.as_deref().unwrap_or("").len()
-                }
-            })
+            .map(|v| v.text().chars().count())
             .sum()
     }
 
-    /// Translate a textual span into the span of the text containing markup
+    /// Obtains the original document, including markup
+    pub fn source(&self) -> String {
+        self.annotation
+            .iter()
+            .map(AnnotationElement::source)
+            .collect()
+    }
+
+    /// Append text, merging it with the previous element if possible
+    pub fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(AnnotationElement::Text { text: last }) = self.annotation.last_mut() {
+            last.push_str(text);
+        } else {
+            self.annotation
+                .push(AnnotationElement::Text { text: text.into() });
+        }
+    }
+
+    /// Append markup, merging it with the previous element if possible.
+    /// Whitespace markup is added as text, as it would be interpreted as such anyway.
+    pub fn push_markup(&mut self, markup: &str, interpret_as: Option<&str>) {
+        if markup.is_empty() && interpret_as.is_none_or(str::is_empty) {
+            return;
+        }
+        if !markup.is_empty() && markup.trim().is_empty() {
+            return self.push_text(markup);
+        }
+        if let Some(AnnotationElement::Markup {
+            markup: last,
+            interpret_as: last_interpret_as,
+        }) = self.annotation.last_mut()
+        {
+            last.push_str(markup);
+            if let Some(interpret_as) = interpret_as {
+                last_interpret_as
+                    .get_or_insert_with(Default::default)
+                    .push_str(interpret_as);
+            }
+        } else {
+            self.annotation.push(AnnotationElement::Markup {
+                markup: markup.into(),
+                interpret_as: interpret_as.map(String::from),
+            });
+        }
+    }
+
+    /// Translate a textual span (in characters) into the span of the text containing markup.
+    /// Spans starting or ending in markup are extended to cover the whole markup.
     pub fn translate_span(&self, start: usize, end: usize) -> (usize, usize) {
         let mut text_offset = 0;
         let mut markup_offset = 0;
@@ -131,46 +265,148 @@ impl Annotations {
         let mut mapped_end: Option<usize> = None;
 
         for annotation in &self.annotation {
-            let (fragment_text_len, fragment_markup_len) = match annotation {
-                AnnotationElement::Text { text } => (text.len(), text.len()),
-                AnnotationElement::Markup { markup, .. }
-                    if !markup.is_empty() && markup.trim().is_empty() =>
-                {
-                    (markup.len(), markup.len())
-                }
-                AnnotationElement::Markup {
-                    markup,
-                    interpret_as,
-                } => (interpret_as.as_deref().unwrap_or("").len(), markup.len()),
+            let fragment_text_len = annotation.text().chars().count();
+            let fragment_markup_len = annotation.source().chars().count();
+            // Whether the text appears as such in the original document
+            let verbatim = annotation.text() == annotation.source();
+            let contains = |position: usize| {
+                position >= text_offset && position < text_offset + fragment_text_len
             };
 
-            let try_set_if_none = |mapped: &mut Option<usize>, original: usize| {
-                if mapped.is_none()
-                    && (original >= text_offset)
-                    && (original < text_offset + fragment_text_len)
-                {
-                    *mapped = Some(markup_offset + (original - text_offset));
-                }
-            };
-            try_set_if_none(&mut mapped_start, start);
-            try_set_if_none(&mut mapped_end, end);
+            if mapped_start.is_none() && contains(start) {
+                mapped_start = Some(if verbatim {
+                    markup_offset + (start - text_offset)
+                } else {
+                    markup_offset
+                });
+            }
+            // The end is exclusive, so we locate the last character of the span.
+            if mapped_end.is_none() && end > start && contains(end - 1) {
+                mapped_end = Some(if verbatim {
+                    markup_offset + (end - text_offset)
+                } else {
+                    markup_offset + fragment_markup_len
+                });
+            }
 
             text_offset += fragment_text_len;
             markup_offset += fragment_markup_len;
         }
+        let mapped_start = mapped_start.unwrap_or(if start == 0 { 0 } else { markup_offset });
         (
-            mapped_start.unwrap_or(0),
-            mapped_end.unwrap_or(markup_offset),
+            mapped_start,
+            if end == start {
+                mapped_start
+            } else {
+                mapped_end.unwrap_or(markup_offset)
+            },
         )
     }
+
+    /// Split into chunks of at most `max_len` characters of text, preferably after paragraphs,
+    /// then sentences, then words, and never inside markup. Each chunk comes with its offset in
+    /// the original document.
+    pub fn split(&self, max_len: usize) -> Vec<(usize, Annotations)> {
+        let chars: Vec<char> = self.text().chars().collect();
+        // Text offsets inside markup, where no cut is possible
+        let mut inside_markup = vec![false; chars.len() + 1];
+        let mut offset = 0;
+        for element in &self.annotation {
+            let len = element.text().chars().count();
+            if let AnnotationElement::Markup { .. } = element {
+                inside_markup[offset + 1..offset + len.max(1)].fill(true);
+            }
+            offset += len;
+        }
+        let mut cuts = vec![];
+        let mut start = 0;
+        while chars.len() - start > max_len.max(1) {
+            let end = start + max_len.max(1);
+            // After paragraphs, then sentences, then words, then anywhere
+            let rank = |p: usize| match (p.checked_sub(2).map(|i| chars[i]), chars[p - 1]) {
+                (Some('\n'), '\n') => 3,
+                (Some('.' | '!' | '?'), c) if c.is_whitespace() => 2,
+                (_, c) if c.is_whitespace() => 1,
+                _ => 0,
+            };
+            let cut = (start + 1..=end)
+                .filter(|p| !inside_markup[*p])
+                .max_by_key(|p| (rank(*p), *p))
+                // Markup longer than the limit
+                .unwrap_or_else(|| {
+                    (end..chars.len())
+                        .find(|p| !inside_markup[*p])
+                        .unwrap_or(chars.len())
+                });
+            cuts.push(cut);
+            start = cut;
+        }
+
+        let mut chunks = vec![];
+        let mut chunk = Annotations::default();
+        let mut chunk_start = 0;
+        let (mut text_offset, mut source_offset) = (0, 0);
+        let mut cuts = cuts.into_iter().peekable();
+        for element in &self.annotation {
+            let len = element.text().chars().count();
+            if let AnnotationElement::Text { text } = element {
+                let mut rest = text.as_str();
+                let mut rest_offset = text_offset;
+                while let Some(cut) = cuts.next_if(|c| *c < text_offset + len) {
+                    let split = rest
+                        .char_indices()
+                        .nth(cut - rest_offset)
+                        .map_or(rest.len(), |(i, _)| i);
+                    chunk.push_text(&rest[..split]);
+                    source_offset += cut - rest_offset;
+                    chunks.push((chunk_start, std::mem::take(&mut chunk)));
+                    chunk_start = source_offset;
+                    rest = &rest[split..];
+                    rest_offset = cut;
+                }
+                chunk.push_text(rest);
+                source_offset += len - (rest_offset - text_offset);
+            } else {
+                chunk.annotation.push(element.clone());
+                source_offset += element.source().chars().count();
+            }
+            text_offset += len;
+            if cuts.next_if_eq(&text_offset).is_some() {
+                chunks.push((chunk_start, std::mem::take(&mut chunk)));
+                chunk_start = source_offset;
+            }
+        }
+        if !chunk.annotation.is_empty() || chunks.is_empty() {
+            chunks.push((chunk_start, chunk));
+        }
+        chunks
+    }
 }
 
 /// API request. Either text or data need to be provided
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Request {
     text: Option<String>,
     data: Option<String>,
     language: String,
+    /// Comma-separated prefixes of rule IDs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disabled_rules: Option<String>,
+    /// Comma-separated category IDs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disabled_categories: Option<String>,
+    /// Format of `text`, which gets converted into annotations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Format>,
+}
+
+fn split_list(list: &Option<String>) -> Vec<String> {
+    list.iter()
+        .flat_map(|l| l.split(','))
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
 }
 
 impl Request {
@@ -179,8 +415,50 @@ impl Request {
             text: Some(text),
             data: None,
             language: language.into(),
+            disabled_rules: None,
+            disabled_categories: None,
+            format: None,
         }
     }
+    /// Request with text and markup, as `data`
+    pub fn from_annotations<S: Into<String>>(annotations: &Annotations, language: S) -> Self {
+        Self {
+            text: None,
+            data: Some(serde_json::to_string(annotations).unwrap()),
+            ..Self::new(String::new(), language)
+        }
+    }
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+    /// Disable the rules whose IDs start with any of the given prefixes
+    pub fn with_disabled_rules(mut self, prefixes: &[String]) -> Self {
+        if !prefixes.is_empty() {
+            self.disabled_rules = Some(prefixes.join(","));
+        }
+        self
+    }
+    /// Disable the categories with the given IDs
+    pub fn with_disabled_categories(mut self, categories: &[String]) -> Self {
+        if !categories.is_empty() {
+            self.disabled_categories = Some(categories.join(","));
+        }
+        self
+    }
+    /// Predicate of whether a match should be reported, given the disabled rules and categories
+    pub fn match_filter(&self) -> impl Fn(&Match) -> bool + Send + 'static {
+        let rules = split_list(&self.disabled_rules);
+        let categories = split_list(&self.disabled_categories);
+        move |m| !m.rule.matches_any(&rules) && !categories.iter().any(|c| m.rule.in_category(c))
+    }
+    /// Length in characters of the text or data, before any conversion
+    pub fn input_len(&self) -> usize {
+        self.text
+            .as_ref()
+            .or(self.data.as_ref())
+            .map_or(0, |s| s.chars().count())
+    }
     pub fn language(&self) -> Language {
         if self.language == "auto" {
             return Default::default();
@@ -193,11 +471,7 @@ impl Request {
 
     pub fn annotations(&self) -> anyhow::Result<Annotations> {
         if let Some(text) = &self.text {
-            Ok(Annotations {
-                annotation: vec![AnnotationElement::Text {
-                    text: text.to_string(),
-                }],
-            })
+            Ok(self.format.unwrap_or_default().to_annotations(text))
         } else if let Some(data) = &self.data {
             let data: Data = serde_json::from_str(data)
                 .with_context(|| format!("Unexpected json contents in `data`: {:?}", data))?;
@@ -213,6 +487,53 @@ impl Request {
         }
     }
 }
+/// Request to add a word to, or delete a word from, the custom dictionary
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WordRequest {
+    pub word: String,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WordsResponse {
+    pub words: Vec<String>,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddWordResponse {
+    pub added: bool,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteWordResponse {
+    pub deleted: bool,
+}
+
+/// Element of the `/v2/languages` response
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageInfo {
+    pub name: String,
+    /// E.g. `en`
+    pub code: String,
+    /// E.g. `en-US`
+    pub long_code: String,
+}
+impl From<&Language> for LanguageInfo {
+    fn from(source: &Language) -> Self {
+        Self {
+            name: if source.name.is_empty() {
+                source.code.clone()
+            } else {
+                source.name.clone()
+            },
+            code: source
+                .code
+                .split('-')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            long_code: source.code.clone(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Response {
     pub matches: Vec<Match>,
@@ -247,13 +568,17 @@ pub struct RuleCategory {
     name: String,
 }
 #[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+pub struct RuleUrl {
+    pub value: String,
+}
+#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Rule {
     pub id: String,
     sub_id: usize,
     description: String,
     issue_type: String,
-    urls: Option<Vec<String>>,
+    urls: Option<Vec<RuleUrl>>,
     category: RuleCategory,
     is_premium: bool,
 }
@@ -261,6 +586,21 @@ impl Rule {
     pub fn is_spelling(&self) -> bool {
         self.id == "MORFOLOGIK_RULE"
     }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn issue_type(&self) -> &str {
+        &self.issue_type
+    }
+    /// Links to explanations of the rule
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.urls.iter().flatten().map(|u| u.value.as_str())
+    }
+    /// Set the link to an explanation of the rule
+    pub fn with_url(mut self, url: Option<&str>) -> Self {
+        self.urls = url.map(|u| vec![RuleUrl { value: u.into() }]);
+        self
+    }
     pub fn spelling() -> Self {
         Self {
             // This will get rendered by the browser extension as a spelling error
@@ -282,6 +622,44 @@ impl Rule {
         }
     }
 
+    pub fn typography(id: &str) -> Self {
+        Self {
+            id: id.into(),
+            issue_type: "typographical".into(),
+            category: RuleCategory {
+                id: "TYPOGRAPHY".into(),
+                name: "Typography".into(),
+            },
+            ..Default::default()
+        }
+    }
+    pub fn inclusive() -> Self {
+        Self {
+            id: "INCLUSIVE_LANGUAGE".into(),
+            issue_type: "style".into(),
+            category: RuleCategory {
+                id: "INCLUSIVE_LANGUAGE".into(),
+                name: "Inclusive language".into(),
+            },
+            ..Default::default()
+        }
+    }
+    /// Whether the rule belongs to the category, either explicitly or through its ID (for
+    /// [`nlprule`] rules, e.g. `TYPOGRAPHY/EN_QUOTES`).
+    pub fn in_category(&self, category: &str) -> bool {
+        self.category.id == category
+            || self
+                .id
+                .split_once('/')
+                .is_some_and(|(prefix, _)| prefix == category)
+    }
+    /// Whether the rule ID starts with any of the given (non-empty) prefixes
+    pub fn matches_any(&self, prefixes: &[String]) -> bool {
+        prefixes
+            .iter()
+            .any(|p| !p.is_empty() && self.id.starts_with(p.as_str()))
+    }
+
     pub fn from_id(id: String) -> Self {
         Self {
             id,
@@ -330,14 +708,13 @@ pub struct Match {
 
 impl Match {
     /// Remove likely false positives
+    #[deprecated(note = "use `Checkers::disabled_rules`, or `Rule::matches_any` with \
+                         `checkers::DEFAULT_DISABLED_RULES`")]
     pub fn filter(&self) -> bool {
-        let rule = &self.rule.id;
-        [
-            "TYPOGRAPHY/EN_QUOTES",
-            // This triggers on lists
-            "PUNCTUATION/DASH_RULE",
-        ]
-        .iter()
-        .all(|r| !rule.starts_with(r))
+        let defaults: Vec<String> = crate::checkers::DEFAULT_DISABLED_RULES
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        !self.rule.matches_any(&defaults)
     }
 }
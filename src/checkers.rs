@@ -1,21 +1,40 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 /// LanguageTool rules (using [`nlprule`]) and spell checking (using [`symspell`])
 use std::fmt::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use anyhow::Context;
 use bincode::Options;
 use log::*;
 use serde::{Deserialize, Serialize};
 
+use crate::analysis;
 use crate::api;
+use crate::inclusive;
+use crate::repetition;
+use crate::stats;
+use crate::typography;
 
 /// Maximum edit distance for Symspell lookups
 const MAX_EDIT_DISTANCE: usize = 3;
 
+/// Rules disabled by default, as they produce likely false positives. These are prefixes of rule IDs.
+pub const DEFAULT_DISABLED_RULES: &[&str] = &[
+    "TYPOGRAPHY/EN_QUOTES",
+    // This triggers on lists
+    "PUNCTUATION/DASH_RULE",
+];
+
+/// Default location of the custom dictionary, in the data directory of the user
+pub fn default_dictionary() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("ltapiserv-rs").join("dictionary.txt"))
+}
+
 /// Convert an nlprule suggestion to an [`api::Match`]
 fn suggestion_to_match(
     source: nlprule::types::Suggestion,
+    url: Option<&str>,
     annotation: &api::Annotations,
 ) -> api::Match {
     debug!("Grammar: {:#?}", source);
@@ -32,7 +51,7 @@ fn suggestion_to_match(
             .collect(),
         offset: start,
         length: end - start,
-        rule: api::Rule::from_id(source.source().into()),
+        rule: api::Rule::from_id(source.source().into()).with_url(url),
         ..Default::default()
     }
 }
@@ -44,6 +63,24 @@ pub struct Checkers {
     spelling: symspell::SymSpell<symspell::AsciiStringStrategy>,
     custom_dictionary: HashSet<String>,
     pub language: api::Language,
+    #[serde(skip)]
+    pub repetition: repetition::Config,
+    #[serde(skip)]
+    pub typography: typography::Config,
+    #[serde(skip)]
+    pub inclusive: inclusive::Terms,
+    /// Prefixes of the IDs of rules that should not be reported
+    #[serde(skip, default = "default_disabled_rules")]
+    pub disabled_rules: Vec<String>,
+    /// Links of the [`nlprule`] rules that have one, by ID, collected on first use
+    #[serde(skip)]
+    rule_urls: OnceLock<HashMap<String, String>>,
+}
+fn default_disabled_rules() -> Vec<String> {
+    DEFAULT_DISABLED_RULES
+        .iter()
+        .map(|r| r.to_string())
+        .collect()
 }
 impl Checkers {
     /// Initialize from a tar.gz archive containing a {language_code}/ folder with:
@@ -71,6 +108,11 @@ impl Checkers {
             custom_dictionary: Default::default(),
             spelling,
             language,
+            repetition: Default::default(),
+            typography: Default::default(),
+            inclusive: Default::default(),
+            disabled_rules: default_disabled_rules(),
+            rule_urls: Default::default(),
         })
     }
     pub fn clear_dictionary(&mut self) {
@@ -103,6 +145,54 @@ impl Checkers {
         );
         Ok(())
     }
+    /// Words of the custom dictionary, sorted
+    pub fn dictionary_words(&self) -> Vec<String> {
+        let mut words: Vec<_> = self.custom_dictionary.iter().cloned().collect();
+        words.sort();
+        words
+    }
+    /// Add a word to a custom dictionary file and to the loaded dictionary, returning whether
+    /// it was missing.
+    pub fn add_word(&mut self, filename: impl AsRef<Path>, word: &str) -> anyhow::Result<bool> {
+        let word = word.to_ascii_lowercase();
+        if !self.custom_dictionary.insert(word.clone()) {
+            return Ok(false);
+        }
+        let filename = filename.as_ref();
+        let mut contents = std::fs::read_to_string(filename).unwrap_or_default();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        writeln!(contents, "{}", word)?;
+        std::fs::write(filename, contents)
+            .with_context(|| format!("Failed to write dictionary at {:?}", filename))?;
+        info!("Added {:?} to dictionary {:?}", word, filename);
+        Ok(true)
+    }
+    /// Remove a word from a custom dictionary file and from the loaded dictionary, returning
+    /// whether it was present.
+    pub fn delete_word(&mut self, filename: impl AsRef<Path>, word: &str) -> anyhow::Result<bool> {
+        let word = word.to_ascii_lowercase();
+        if !self.custom_dictionary.remove(&word) {
+            return Ok(false);
+        }
+        let filename = filename.as_ref();
+        let contents: String = std::fs::read_to_string(filename)?
+            .lines()
+            .map(|l| {
+                l.split_ascii_whitespace()
+                    .filter(|w| !w.eq_ignore_ascii_case(&word))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|l| !l.is_empty())
+            .map(|l| l + "\n")
+            .collect();
+        std::fs::write(filename, contents)
+            .with_context(|| format!("Failed to write dictionary at {:?}", filename))?;
+        info!("Removed {:?} from dictionary {:?}", word, filename);
+        Ok(true)
+    }
     fn from_archive_bytes_impl(archive: &[u8]) -> anyhow::Result<Self> {
         info!("Subsequent initializations will be significantly faster.");
         // Unpack
@@ -174,25 +264,41 @@ impl Checkers {
         debug!("Saved to cache at {}", cache.display());
         Ok(out)
     }
+    /// Compute readability statistics on a text
+    pub fn stats(&self, annotations: &api::Annotations) -> stats::Stats {
+        stats::Stats::compute(self.tokenizer.pipe(&annotations.text()), annotations)
+    }
+    /// Tokenize and tag a text
+    pub fn analyze(&self, annotations: &api::Annotations) -> Vec<analysis::Sentence> {
+        analysis::Sentence::analyze(self.tokenizer.pipe(&annotations.text()), annotations)
+    }
     /// Compute suggestions on a text
     pub fn suggest(&self, annotations: &api::Annotations) -> Vec<api::Match> {
         let mut suggestions = Vec::new();
 
         let text = annotations.text();
+        let rule_urls = self.rule_urls.get_or_init(|| {
+            self.rules
+                .rules()
+                .iter()
+                .filter_map(|r| Some((r.id().to_string(), r.url()?.to_string())))
+                .collect()
+        });
+        let mut repetitions = repetition::Repetitions::new(&self.repetition, annotations);
         for sentence in self.tokenizer.pipe(&text) {
             debug!("Processing sentence {:#?}", sentence);
 
             // Grammar suggestions from nlprule
-            suggestions.extend(
-                self.rules
-                    .apply(&sentence)
-                    .into_iter()
-                    .map(|s| suggestion_to_match(s, annotations))
-                    .filter(api::Match::filter),
-            );
+            suggestions.extend(self.rules.apply(&sentence).into_iter().map(|s| {
+                let url = rule_urls.get(s.source()).map(String::as_str);
+                suggestion_to_match(s, url, annotations)
+            }));
             // Spelling and repetitions, processing the sentence token by token.
             let tokens = sentence.tokens();
             for (i, token) in tokens.iter().enumerate() {
+                // Repetitions
+                suggestions.extend(repetitions.push(token));
+
                 let word = token.word();
                 let word_str = unidecode::unidecode(word.as_str());
 
@@ -201,22 +307,6 @@ impl Checkers {
                     continue;
                 }
 
-                // Repetitions
-                if let Some((start, end)) =
-                    next_token.filter(|t| t.word() == token.word()).map(|t| {
-                        annotations.translate_span(token.span().start().char, t.span().end().char)
-                    })
-                {
-                    suggestions.push(api::Match {
-                        rule: api::Rule::duplication(),
-                        message: "Possible typo: you repeated a word".into(),
-                        replacements: vec![token.into()],
-                        offset: start,
-                        length: end - start,
-                        ..Default::default()
-                    })
-                }
-
                 let word_str_lowercase = word_str.to_lowercase();
                 // Spelling
                 if !(self.custom_dictionary.contains(&word_str_lowercase)
@@ -264,6 +354,9 @@ impl Checkers {
                 }
             }
         }
+        suggestions.extend(typography::check(&self.typography, annotations));
+        suggestions.extend(self.inclusive.check(annotations));
+        suggestions.retain(|m| !m.rule.matches_any(&self.disabled_rules));
         debug!("{:#?}", suggestions);
         suggestions
     }
@@ -0,0 +1,132 @@
+/// Where the texts are checked: a server, or checkers loaded in-process from a data archive with
+/// `--archive`, without a running server.
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::*;
+use tokio::sync::RwLock;
+
+use ltapiserv_rs::analysis;
+use ltapiserv_rs::api::{Annotations, Request, Response};
+use ltapiserv_rs::checkers::{self, Checkers};
+use ltapiserv_rs::client::Client;
+use ltapiserv_rs::stats::Stats;
+
+use super::Flags;
+
+pub enum Backend {
+    Server(Client),
+    Local {
+        checkers: Arc<RwLock<Checkers>>,
+        /// Custom dictionary, shared with the server
+        dictionary: Option<PathBuf>,
+    },
+}
+
+impl Backend {
+    pub async fn new(args: &Flags) -> anyhow::Result<Self> {
+        let Some(archive) = args.archive.clone() else {
+            let url = args
+                .server
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("A server or an archive is required"))?;
+            return Ok(Backend::Server(
+                Client::new(url).with_timeout(Duration::from_secs(args.timeout)),
+            ));
+        };
+        let start = std::time::Instant::now();
+        info!("Loading {:?}...", archive);
+        let dictionary = checkers::default_dictionary();
+        let checkers = tokio::task::spawn_blocking({
+            let dictionary = dictionary.clone();
+            move || -> anyhow::Result<Checkers> {
+                let mut checkers = Checkers::from_archive(&archive)?;
+                if let Some(dictionary) = &dictionary {
+                    checkers.add_dictionary(dictionary)?;
+                }
+                Ok(checkers)
+            }
+        })
+        .await??;
+        info!(
+            "Loaded the {} checkers in {:?}",
+            checkers.language,
+            start.elapsed()
+        );
+        Ok(Backend::Local {
+            checkers: Arc::new(RwLock::new(checkers)),
+            dictionary,
+        })
+    }
+
+    /// Run the checkers on the annotations of a request, in a blocking task
+    async fn local<T: Send + 'static>(
+        checkers: &Arc<RwLock<Checkers>>,
+        request: &Request,
+        f: impl FnOnce(&Checkers, Annotations) -> T + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let checkers = checkers.clone().read_owned().await;
+        anyhow::ensure!(
+            request.language() == checkers.language,
+            "Unsupported language {} (the archive supports {})",
+            request.language(),
+            checkers.language
+        );
+        let annotations = request.annotations()?;
+        Ok(tokio::task::spawn_blocking(move || f(&checkers, annotations)).await?)
+    }
+
+    pub async fn check(&self, request: &Request) -> anyhow::Result<Response> {
+        match self {
+            Backend::Server(client) => Ok(client.check(request).await?),
+            Backend::Local { checkers, .. } => {
+                let (matches, language) = Self::local(checkers, request, |checkers, a| {
+                    (checkers.suggest(&a), checkers.language.clone())
+                })
+                .await?;
+                Ok(Response {
+                    matches: matches.into_iter().filter(request.match_filter()).collect(),
+                    language: language.into(),
+                })
+            }
+        }
+    }
+
+    pub async fn stats(&self, request: &Request) -> anyhow::Result<Stats> {
+        match self {
+            Backend::Server(client) => Ok(client.stats(request).await?),
+            Backend::Local { checkers, .. } => {
+                Self::local(checkers, request, |checkers, a| checkers.stats(&a)).await
+            }
+        }
+    }
+
+    pub async fn analyze(&self, request: &Request) -> anyhow::Result<Vec<analysis::Sentence>> {
+        match self {
+            Backend::Server(client) => Ok(client.analyze(request).await?),
+            Backend::Local { checkers, .. } => {
+                Self::local(checkers, request, |checkers, a| checkers.analyze(&a)).await
+            }
+        }
+    }
+
+    /// Add a word to the custom dictionary
+    pub async fn add_word(&self, word: &str) -> anyhow::Result<()> {
+        match self {
+            Backend::Server(client) => {
+                client.add_word(word).await?;
+            }
+            Backend::Local {
+                checkers,
+                dictionary,
+            } => {
+                let dictionary = dictionary
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("No location for the custom dictionary"))?;
+                checkers.write().await.add_word(dictionary, word)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+/// Baseline of known matches, which are not reported, so that only new ones fail a CI job.
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::report::{self, Checked};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Entry {
+    file: String,
+    rule: String,
+    /// Matched text, for readability
+    text: String,
+    fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Baseline {
+    matches: Vec<Entry>,
+}
+
+impl Baseline {
+    /// Record the matches of the given files
+    pub fn new(files: &[Checked]) -> Self {
+        let mut matches: Vec<Entry> = files
+            .iter()
+            .flat_map(|c| {
+                c.response.matches.iter().map(|m| Entry {
+                    file: c.name.clone(),
+                    rule: report::rule_id(m),
+                    text: report::matched_text(&c.text, m),
+                    fingerprint: report::fingerprint(&c.name, &c.text, m),
+                })
+            })
+            .collect();
+        matches.sort();
+        Self { matches }
+    }
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Could not read baseline {:?}, create it with --update-baseline",
+                path
+            )
+        })?;
+        serde_json::from_str(&contents).with_context(|| format!("Invalid baseline {:?}", path))
+    }
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Could not write baseline {:?}", path))
+    }
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+    /// Remove the matches recorded in the baseline from the results of a file, returning their
+    /// number. Each entry of the baseline accounts for at most one match.
+    pub fn filter(&self, checked: &mut Checked) -> usize {
+        let mut known: HashMap<&str, usize> = HashMap::new();
+        for entry in self.matches.iter().filter(|e| e.file == checked.name) {
+            *known.entry(&entry.fingerprint).or_default() += 1;
+        }
+        let before = checked.response.matches.len();
+        let (name, text) = (&checked.name, &checked.text);
+        checked.response.matches.retain(|m| {
+            match known.get_mut(report::fingerprint(name, text, m).as_str()) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+        before - checked.response.matches.len()
+    }
+}
@@ -0,0 +1,199 @@
+/// Project configuration from a `.ltapi.toml` file, found in the working directory or one of its
+/// parents. Command line arguments and environment variables take precedence.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::*;
+use serde::Deserialize;
+
+use super::report::{self, Checked};
+
+pub const FILENAME: &str = ".ltapi.toml";
+
+/// Language of the files matching a glob
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LanguageGlob {
+    glob: String,
+    language: String,
+}
+
+/// Globs and paths are relative to the directory of the configuration file.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Directory of the configuration file
+    #[serde(skip)]
+    root: PathBuf,
+    server: Option<String>,
+    /// Default language
+    language: Option<String>,
+    /// Languages of the files matching globs, the first match taking precedence
+    #[serde(default)]
+    languages: Vec<LanguageGlob>,
+    /// Output format
+    format: Option<String>,
+    suggestions: Option<usize>,
+    /// Prefixes of the IDs of rules to disable
+    #[serde(default)]
+    pub disabled_rules: Vec<String>,
+    /// Project dictionary, with one word per line
+    dictionary: Option<PathBuf>,
+    /// Files to check when none are given on the command line
+    #[serde(default)]
+    include: Vec<String>,
+    /// Files to skip
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Words of the dictionary, lowercased
+    #[serde(skip)]
+    words: HashSet<String>,
+}
+
+fn glob_set<'a>(globs: impl Iterator<Item = &'a str>) -> anyhow::Result<globset::GlobSet> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(
+            globset::GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+impl Config {
+    /// Find and load the configuration file, if any
+    pub fn discover() -> anyhow::Result<Option<Self>> {
+        let cwd = std::env::current_dir()?;
+        let Some(path) = cwd
+            .ancestors()
+            .map(|d| d.join(FILENAME))
+            .find(|p| p.is_file())
+        else {
+            return Ok(None);
+        };
+        Self::load(&path).map(Some)
+    }
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("Loading configuration from {:?}", path);
+        let mut config: Self = toml::from_str(&std::fs::read_to_string(path)?)
+            .with_context(|| format!("Invalid configuration {:?}", path))?;
+        config.root = path.parent().unwrap().to_path_buf();
+        if let Some(dictionary) = &config.dictionary {
+            let dictionary = config.root.join(dictionary);
+            config.words = std::fs::read_to_string(&dictionary)
+                .with_context(|| format!("Could not read dictionary {:?}", dictionary))?
+                .lines()
+                .flat_map(str::split_whitespace)
+                .map(str::to_lowercase)
+                .collect();
+        }
+        Ok(config)
+    }
+
+    /// Use the configuration as the default values of the command line arguments
+    pub fn defaults(&self, mut command: clap::Command) -> clap::Command {
+        let defaults = [
+            ("server", self.server.clone()),
+            ("format", self.format.clone()),
+            ("suggestions", self.suggestions.map(|s| s.to_string())),
+        ];
+        for (arg, value) in defaults {
+            if let Some(value) = value {
+                command = command.mut_arg(arg, |a| a.default_value(value).required(false));
+            }
+        }
+        command
+    }
+
+    /// Path relative to the directory of the configuration file
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        // Resolve `..` lexically, as for the globs
+        let mut absolute = PathBuf::new();
+        for component in std::path::absolute(path).ok()?.components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    absolute.pop();
+                }
+                c => absolute.push(c),
+            }
+        }
+        absolute
+            .strip_prefix(&self.root)
+            .ok()
+            .map(Path::to_path_buf)
+    }
+
+    pub fn language(&self, filename: Option<&Path>) -> anyhow::Result<Option<String>> {
+        if let Some(relative) = filename.and_then(|f| self.relative(f)) {
+            for l in &self.languages {
+                if glob_set(std::iter::once(l.glob.as_str()))?.is_match(&relative) {
+                    return Ok(Some(l.language.clone()));
+                }
+            }
+        }
+        Ok(self.language.clone())
+    }
+
+    /// Globs of the files to check, if none are given on the command line
+    pub fn include(&self) -> Vec<PathBuf> {
+        // Relative to the working directory if possible, for shorter names in the reports
+        let root = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| {
+                cwd.strip_prefix(&self.root)
+                    .ok()
+                    .map(|p| p.components().map(|_| "..").collect())
+            })
+            .unwrap_or_else(|| self.root.clone());
+        self.include.iter().map(|g| root.join(g)).collect()
+    }
+
+    /// Remove the excluded files
+    pub fn exclude(&self, files: Vec<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
+        let exclude = glob_set(self.exclude.iter().map(String::as_str))?;
+        Ok(files
+            .into_iter()
+            .filter(|f| !self.relative(f).is_some_and(|r| exclude.is_match(r)))
+            .collect())
+    }
+
+    /// Remove the spelling matches of words in the project dictionary, returning their number
+    pub fn filter(&self, checked: &mut Checked) -> usize {
+        let before = checked.response.matches.len();
+        let text = &checked.text;
+        checked.response.matches.retain(|m| {
+            !(m.rule.is_spelling()
+                && self
+                    .words
+                    .contains(&report::matched_text(text, m).to_lowercase()))
+        });
+        before - checked.response.matches.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn languages() -> anyhow::Result<()> {
+        let mut config: Config = toml::from_str(
+            r#"
+            language = "en-GB"
+            [[languages]]
+            glob = "docs/de/**"
+            language = "de-DE"
+            "#,
+        )?;
+        config.root = "/project".into();
+        let language = |f: &str| config.language(Some(Path::new(f))).unwrap();
+        assert_eq!(language("/project/docs/de/a.md").unwrap(), "de-DE");
+        assert_eq!(language("/project/docs/en/../de/b.md").unwrap(), "de-DE");
+        assert_eq!(language("/project/docs/a.md").unwrap(), "en-GB");
+        assert!(toml::from_str::<Config>("lang = 'en-US'").is_err());
+        Ok(())
+    }
+}
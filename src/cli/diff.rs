@@ -0,0 +1,188 @@
+/// Lines changed according to `git diff`, to only report the matches in modified text.
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::*;
+
+use super::report::{Checked, SourceIndex};
+
+/// Path without a leading `./`, for comparison with the paths output by git
+fn normalize(path: &Path) -> &Path {
+    path.strip_prefix(".").unwrap_or(path)
+}
+
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// Ranges of added or modified lines (1-based) of each file, relative to the working
+    /// directory
+    files: HashMap<PathBuf, Vec<RangeInclusive<usize>>>,
+    /// Whether the files are compared from the index rather than the working tree
+    staged: bool,
+}
+
+/// Start and length of a range of a hunk header, e.g. `10,2` or `10`
+fn range(range: &str) -> Option<(usize, usize)> {
+    Some(match range.split_once(',') {
+        Some((start, length)) => (start.parse().ok()?, length.parse().ok()?),
+        None => (range.parse().ok()?, 1),
+    })
+}
+
+/// Old and new ranges of a hunk header, e.g. `@@ -10,2 +10,3 @@`
+fn hunk(header: &str) -> Option<((usize, usize), (usize, usize))> {
+    let mut ranges = header.split_whitespace().skip(1);
+    let old = range(ranges.next()?.strip_prefix('-')?)?;
+    let new = range(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+impl Changes {
+    /// Parse the output of `git diff --unified=0`
+    fn parse(diff: &str) -> Self {
+        let mut files: HashMap<PathBuf, Vec<_>> = HashMap::new();
+        let mut current = None;
+        // Lines left in the current hunk, which could look like headers
+        let mut remaining = 0;
+        for line in diff.lines() {
+            if remaining > 0 {
+                remaining -= 1;
+            } else if let Some(path) = line.strip_prefix("+++ ") {
+                current = path.strip_prefix("b/").map(PathBuf::from);
+                if let Some(path) = &current {
+                    files.entry(path.clone()).or_default();
+                }
+            } else if let Some(((_, old), (start, new))) =
+                line.strip_prefix("@@ ").and_then(|_| hunk(line))
+            {
+                remaining = old + new;
+                // Pure deletions have no new lines
+                if let (Some(path), 1..) = (&current, new) {
+                    files.get_mut(path).unwrap().push(start..=start + new - 1);
+                }
+            }
+        }
+        Self {
+            files,
+            staged: false,
+        }
+    }
+
+    /// Run `git diff` against a revision (or the index), or of the staged changes
+    pub fn from_git(rev: Option<&str>, staged: bool) -> anyhow::Result<Self> {
+        let mut command = std::process::Command::new("git");
+        command.args([
+            "-c",
+            "core.quotePath=false",
+            "diff",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+            "--relative",
+            "--diff-filter=d",
+        ]);
+        if staged {
+            command.arg("--cached");
+        }
+        command.args(rev).arg("--");
+        debug!("Running {:?}", command);
+        let output = command.output().context("Could not run git")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git diff failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let mut changes = Self::parse(&String::from_utf8_lossy(&output.stdout));
+        changes.staged = staged;
+        Ok(changes)
+    }
+
+    /// Changed files, sorted
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<_> = self.files.keys().cloned().collect();
+        files.sort();
+        files
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(normalize(path))
+    }
+
+    /// Read a changed file, from the index with `--staged`
+    pub fn read(&self, path: &Path) -> anyhow::Result<String> {
+        if !self.staged {
+            return super::inputs::read(Some(path));
+        }
+        let output = std::process::Command::new("git")
+            .arg("show")
+            .arg(format!(":./{}", normalize(path).display()))
+            .output()
+            .context("Could not run git")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Could not read {:?} from the index: {}",
+                path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Remove the matches outside the changed lines, returning their number
+    pub fn filter(&self, checked: &mut Checked) -> usize {
+        let before = checked.response.matches.len();
+        let lines = checked
+            .path
+            .as_deref()
+            .and_then(|p| self.files.get(normalize(p)));
+        let index = SourceIndex::new(&checked.text);
+        checked.response.matches.retain(|m| {
+            let (start, _) = index.position(m.offset);
+            let (end, _) = index.position(m.offset + m.length);
+            lines.is_some_and(|lines| lines.iter().any(|l| *l.start() <= end && start <= *l.end()))
+        });
+        before - checked.response.matches.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let diff = "\
+diff --git a/README.md b/README.md
+index 1111111..2222222 100644
+--- a/README.md
++++ b/README.md
+@@ -3 +3 @@ Title
+-Old line
++New line
+@@ -10,2 +9,0 @@
+-Deleted
+-Deleted
+@@ -20,0 +19,3 @@
++Added
++Added
++Added
+diff --git a/docs/new.md b/docs/new.md
+new file mode 100644
+--- /dev/null
++++ b/docs/new.md
+@@ -0,0 +1,2 @@
++++ Not a header
++Text
+";
+        let changes = Changes::parse(diff);
+        assert_eq!(
+            changes.files(),
+            [Path::new("README.md"), Path::new("docs/new.md")]
+        );
+        assert_eq!(changes.files[Path::new("README.md")], [3..=3, 19..=21]);
+        assert_eq!(changes.files[Path::new("docs/new.md")], [1..=2]);
+        assert!(changes.contains(Path::new("./docs/new.md")));
+    }
+}
@@ -0,0 +1,208 @@
+/// Application of the suggested replacements, automatically or interactively.
+use std::collections::HashSet;
+use std::io::Write;
+
+use itertools::Itertools;
+use log::*;
+
+use ltapiserv_rs::api::Match;
+
+use super::backend::Backend;
+use super::report::{self, Checked};
+use super::Flags;
+
+/// Replacement of a span of characters
+struct Edit {
+    start: usize,
+    end: usize,
+    replacement: String,
+}
+
+impl Edit {
+    fn new(m: &Match, replacement: usize) -> Self {
+        Self {
+            start: m.offset,
+            end: m.offset + m.length,
+            replacement: m.replacements[replacement].value.clone(),
+        }
+    }
+}
+
+fn overlaps(edits: &[Edit], m: &Match) -> bool {
+    edits
+        .iter()
+        .any(|e| m.offset < e.end && e.start < m.offset + m.length)
+}
+
+/// Apply non-overlapping edits, sorted by position
+fn apply(text: &str, edits: &[Edit]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    let mut pos = 0;
+    for edit in edits {
+        out.extend(chars.by_ref().take(edit.start - pos));
+        out.push_str(&edit.replacement);
+        chars.by_ref().take(edit.end - edit.start).for_each(drop);
+        pos = edit.end;
+    }
+    out.extend(chars);
+    out
+}
+
+/// Write the edited file back, or print a unified diff with `--dry-run`. The edited text is
+/// printed for stdin.
+fn save(args: &Flags, checked: &Checked, mut edits: Vec<Edit>) -> anyhow::Result<()> {
+    edits.sort_by_key(|e| e.start);
+    let fixed = apply(&checked.text, &edits);
+    if args.dry_run {
+        print!(
+            "{}",
+            similar::TextDiff::from_lines(&checked.text, &fixed)
+                .unified_diff()
+                .header(&checked.name, &checked.name)
+        );
+    } else if let Some(path) = &checked.path {
+        std::fs::write(path, fixed)?;
+        info!("{}: applied {} replacements", checked.name, edits.len());
+    } else {
+        print!("{}", fixed);
+    }
+    Ok(())
+}
+
+/// Apply the first replacement of each match that does not overlap a previous one, returning
+/// the number of matches left.
+pub fn fix(args: &Flags, files: &[Checked]) -> anyhow::Result<usize> {
+    let mut remaining = 0;
+    for checked in files {
+        let mut edits = vec![];
+        for m in checked.response.matches.iter().sorted_by_key(|m| m.offset) {
+            if m.replacements.is_empty() || overlaps(&edits, m) {
+                remaining += 1;
+            } else {
+                edits.push(Edit::new(m, 0));
+            }
+        }
+        if !edits.is_empty() {
+            save(args, checked, edits)?;
+        }
+    }
+    Ok(remaining)
+}
+
+enum Choice {
+    Replace(usize),
+    Skip,
+    Dictionary,
+    Quit,
+}
+
+/// Prompt for a choice, offering to add the word to the dictionary if `dictionary` is set
+fn prompt(m: &Match, dictionary: bool) -> anyhow::Result<Choice> {
+    let replacements = m.replacements.len().min(9);
+    for (i, r) in m.replacements.iter().take(replacements).enumerate() {
+        println!("  {}) {}", i + 1, r.value);
+    }
+    loop {
+        match replacements {
+            0 => {}
+            1 => print!("[a]ccept, "),
+            n => print!("[a]ccept, [1-{}] choose, ", n),
+        }
+        print!("[s]kip, ");
+        if dictionary {
+            print!("add to [d]ictionary, ");
+        }
+        print!("[q]uit: ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            return Ok(Choice::Quit);
+        }
+        match line.trim() {
+            "a" if replacements > 0 => return Ok(Choice::Replace(0)),
+            "s" => return Ok(Choice::Skip),
+            "d" if dictionary => return Ok(Choice::Dictionary),
+            "q" => return Ok(Choice::Quit),
+            n => match n.parse::<usize>() {
+                Ok(i) if (1..=replacements).contains(&i) => return Ok(Choice::Replace(i - 1)),
+                _ => println!("Invalid choice {:?}", n),
+            },
+        }
+    }
+}
+
+/// Review the matches one by one, returning the number of matches left.
+pub async fn interactive(
+    args: &Flags,
+    backend: &Backend,
+    files: &[Checked],
+) -> anyhow::Result<usize> {
+    let mut remaining = 0;
+    let mut quit = false;
+    // Words added to the dictionary, whose other occurrences are skipped
+    let mut added: HashSet<String> = Default::default();
+    for checked in files {
+        let source = report::named_source(checked);
+        let index = report::SourceIndex::new(&checked.text);
+        let mut edits = vec![];
+        for m in checked.response.matches.iter().sorted_by_key(|m| m.offset) {
+            let matched = report::matched_text(&checked.text, m).to_lowercase();
+            if quit || overlaps(&edits, m) {
+                remaining += 1;
+                continue;
+            }
+            if m.rule.is_spelling() && added.contains(&matched) {
+                continue;
+            }
+            report::pretty_match(&source, &index, m, args.suggestions);
+            // Only single misspelled words can be added to the dictionary
+            let word = m.rule.is_spelling()
+                && !matched.is_empty()
+                && !matched.contains(char::is_whitespace);
+            match prompt(m, word)? {
+                Choice::Replace(i) => edits.push(Edit::new(m, i)),
+                Choice::Skip => remaining += 1,
+                Choice::Dictionary => match backend.add_word(&matched).await {
+                    Ok(()) => {
+                        info!("Added {:?} to the dictionary", matched);
+                        added.insert(matched);
+                    }
+                    Err(e) => {
+                        error!("Failed to add {:?} to the dictionary: {:#}", matched, e);
+                        remaining += 1;
+                    }
+                },
+                Choice::Quit => {
+                    quit = true;
+                    remaining += 1;
+                }
+            }
+        }
+        if !edits.is_empty() {
+            save(args, checked, edits)?;
+        }
+    }
+    Ok(remaining)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_edits() {
+        let edit = |start, end, replacement: &str| Edit {
+            start,
+            end,
+            replacement: replacement.into(),
+        };
+        assert_eq!(
+            apply(
+                "Thé cat sat on on the mat",
+                &[edit(0, 3, "The"), edit(12, 15, ""), edit(22, 25, "rug")]
+            ),
+            "The cat sat on the rug"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+/// Files to check, from paths, directories and globs.
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use itertools::Itertools;
+use log::*;
+
+use ltapiserv_rs::formats::Format;
+
+/// Whether a file found in a directory is checked: plain text and markup documents, but not
+/// source code, which has to be given explicitly.
+fn is_document(path: &Path) -> bool {
+    matches!(
+        Format::from_path(path),
+        Some(Format::Markdown | Format::Latex | Format::Html | Format::Xml)
+    ) || path.extension().is_some_and(|e| e == "txt")
+}
+
+/// Whether a file can be checked, as a document or source code
+pub fn is_supported(path: &Path) -> bool {
+    Format::from_path(path).is_some() || path.extension().is_some_and(|e| e == "txt")
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '{'])
+}
+
+/// Files below a directory, respecting `.gitignore` and hidden files
+fn walk(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    ignore::Walk::new(dir)
+        .filter_map(|entry| entry.map_err(|e| warn!("{}", e)).ok())
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .map(ignore::DirEntry::into_path)
+}
+
+/// Files matching a glob, searched from its longest directory prefix without wildcards
+fn glob(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let matcher = globset::GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher();
+    let base: PathBuf = Path::new(pattern)
+        .components()
+        .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+        .collect();
+    Ok(if base.as_os_str().is_empty() {
+        walk(Path::new("."))
+            .filter_map(|p| p.strip_prefix(".").ok().map(Path::to_path_buf))
+            .filter(|p| matcher.is_match(p))
+            .sorted()
+            .collect()
+    } else {
+        walk(&base)
+            .filter(|p| matcher.is_match(p))
+            .sorted()
+            .collect()
+    })
+}
+
+/// Expand the paths given on the command line into files: directories are searched for
+/// documents, and globs (e.g. `docs/**/*.md`, if not expanded by the shell) are matched.
+pub fn collect(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            files.extend(walk(path).filter(|p| is_document(p)).sorted());
+        } else if let Some(pattern) = path.to_str().filter(|p| !path.exists() && is_glob(p)) {
+            let matches = glob(pattern)?;
+            if matches.is_empty() {
+                anyhow::bail!("No file matches {:?}", pattern);
+            }
+            files.extend(matches);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files.into_iter().unique().collect())
+}
+
+/// Read a file, or stdin if there is no filename
+pub fn read(filename: Option<&Path>) -> anyhow::Result<String> {
+    Ok(if let Some(filename) = filename {
+        std::fs::read_to_string(filename)
+            .with_context(|| format!("Could not open {:?}", filename))?
+    } else {
+        info!("Reading from stdin",);
+        std::io::read_to_string(std::io::stdin())?
+    })
+}
+
+/// Name of an input, for reports
+pub fn name(filename: Option<&Path>) -> String {
+    filename.map_or_else(|| "<stdin>".into(), |f| f.display().to_string())
+}
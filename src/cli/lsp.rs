@@ -0,0 +1,409 @@
+/// Language server over stdio: the open documents are checked when they change, and the matches
+/// are published as diagnostics, with code actions applying the replacements, adding words to
+/// the dictionary, or disabling rules for the session.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use log::*;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+use ltapiserv_rs::formats::Format;
+
+use super::backend::Backend;
+use super::report::{self, Checked, Severity};
+use super::Flags;
+
+const ADD_WORD: &str = "ltapi.addToDictionary";
+const DISABLE_RULE: &str = "ltapi.disableRule";
+
+struct Document {
+    text: String,
+    version: i64,
+    format: Format,
+    path: Option<PathBuf>,
+}
+
+struct Server {
+    args: Flags,
+    backend: Backend,
+    /// Messages to write to stdout
+    out: tokio::sync::mpsc::UnboundedSender<Value>,
+    documents: Mutex<HashMap<String, Document>>,
+    /// Prefixes of the rule IDs disabled with a code action
+    disabled_rules: Mutex<Vec<String>>,
+}
+
+/// Format of a document, from its language identifier or its extension
+fn format(language_id: &str, path: Option<&std::path::Path>) -> Format {
+    match language_id {
+        "markdown" => Format::Markdown,
+        "latex" | "tex" => Format::Latex,
+        "html" => Format::Html,
+        "xml" => Format::Xml,
+        "rust" => Format::Rust,
+        "c" | "cpp" | "java" | "csharp" | "kotlin" | "swift" => Format::C,
+        "go" => Format::Go,
+        "javascript" | "javascriptreact" | "typescript" | "typescriptreact" => Format::Javascript,
+        "python" => Format::Python,
+        "shellscript" | "ruby" | "perl" => Format::Shell,
+        "git-commit" | "gitcommit" => Format::Commit,
+        _ => path.and_then(Format::from_path).unwrap_or_default(),
+    }
+}
+
+fn diagnostics(checked: &Checked) -> Vec<Value> {
+    let index = report::SourceIndex::new(&checked.text);
+    let position = |offset: usize| {
+        let (line, character) = index.utf16_position(&checked.text, offset);
+        json!({ "line": line, "character": character })
+    };
+    checked
+        .response
+        .matches
+        .iter()
+        .map(|m| {
+            json!({
+                "range": {
+                    "start": position(m.offset),
+                    "end": position(m.offset + m.length),
+                },
+                "severity": match Severity::of(m) {
+                    Severity::Warning => 2,
+                    Severity::Advice => 3,
+                },
+                "code": report::rule_id(m),
+                "source": "ltapi",
+                "message": m.message,
+                "data": {
+                    "replacements": m.replacements.iter().map(|r| &r.value).collect::<Vec<_>>(),
+                    "word": m.rule.is_spelling().then(|| report::matched_text(&checked.text, m)),
+                    "rule": report::rule_id(m),
+                },
+            })
+        })
+        .collect()
+}
+
+/// Code actions for the diagnostics of a range
+fn code_actions(suggestions: usize, params: &Value) -> Vec<Value> {
+    let uri = &params["textDocument"]["uri"];
+    let empty = vec![];
+    let diagnostics = params["context"]["diagnostics"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter(|d| d["source"] == "ltapi");
+    let mut actions = vec![];
+    for d in diagnostics {
+        let data = &d["data"];
+        let replacements = data["replacements"].as_array().unwrap_or(&empty);
+        for (i, r) in replacements.iter().take(suggestions.max(1)).enumerate() {
+            actions.push(json!({
+                "title": format!("Replace with {}", r.as_str().unwrap_or_default()),
+                "kind": "quickfix",
+                "diagnostics": [d],
+                "isPreferred": i == 0,
+                "edit": { "changes": { uri.as_str().unwrap_or_default(): [
+                    { "range": d["range"], "newText": r }
+                ] } },
+            }));
+        }
+        let mut command = |title: String, command: &str, argument: &Value| {
+            actions.push(json!({
+                "title": title,
+                "kind": "quickfix",
+                "diagnostics": [d],
+                "command": { "title": title, "command": command, "arguments": [argument] },
+            }));
+        };
+        if let Some(word) = data["word"].as_str() {
+            command(
+                format!("Add \"{}\" to the dictionary", word),
+                ADD_WORD,
+                &data["word"],
+            );
+        }
+        if let Some(rule) = data["rule"].as_str().filter(|r| !r.is_empty()) {
+            command(
+                format!("Disable rule {}", rule),
+                DISABLE_RULE,
+                &data["rule"],
+            );
+        }
+    }
+    actions
+}
+
+impl Server {
+    fn send(&self, message: Value) {
+        let _ = self.out.send(message);
+    }
+    fn respond(&self, id: &Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+    fn show_error(&self, error: &anyhow::Error) {
+        error!("{:#}", error);
+        self.notify(
+            "window/showMessage",
+            json!({ "type": 1, "message": format!("ltapi: {:#}", error) }),
+        );
+    }
+
+    /// Check a document and publish its diagnostics, unless it changed in the meantime
+    async fn check(self: Arc<Self>, uri: String) {
+        let Some((text, version, format, path)) = self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&uri)
+            .map(|d| (d.text.clone(), d.version, d.format, d.path.clone()))
+        else {
+            return;
+        };
+        let result = async {
+            let language = self.args.language(path.as_deref())?;
+            super::check_text(&self.args, &self.backend, format, &text, language).await
+        }
+        .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => return self.show_error(&e.context(format!("Could not check {}", uri))),
+        };
+        let mut checked = Checked {
+            path,
+            name: uri.clone(),
+            text,
+            response,
+        };
+        super::filter(&self.args, None, &mut checked);
+        let disabled = self.disabled_rules.lock().unwrap().clone();
+        checked.response.matches.retain(|m| {
+            let rule = report::rule_id(m);
+            !disabled.iter().any(|p| rule.starts_with(p.as_str()))
+        });
+        if self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&uri)
+            .is_some_and(|d| d.version == version)
+        {
+            self.notify(
+                "textDocument/publishDiagnostics",
+                json!({ "uri": uri, "version": version, "diagnostics": diagnostics(&checked) }),
+            );
+        }
+    }
+
+    fn check_all(self: &Arc<Self>) {
+        let uris: Vec<String> = self.documents.lock().unwrap().keys().cloned().collect();
+        for uri in uris {
+            tokio::spawn(self.clone().check(uri));
+        }
+    }
+
+    async fn execute_command(self: Arc<Self>, id: Value, params: Value) {
+        let argument = params["arguments"][0]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+        let result = match params["command"].as_str() {
+            Some(ADD_WORD) => async {
+                let word = argument.to_lowercase();
+                self.backend.add_word(&word).await?;
+                info!("Added {:?} to the dictionary", word);
+                anyhow::Ok(())
+            }
+            .await
+            .context("Could not add the word to the dictionary"),
+            Some(DISABLE_RULE) => {
+                info!("Disabling {} for the session", argument);
+                self.disabled_rules.lock().unwrap().push(argument);
+                Ok(())
+            }
+            command => Err(anyhow::anyhow!("Unknown command {:?}", command)),
+        };
+        match result {
+            Ok(()) => self.check_all(),
+            Err(e) => self.show_error(&e),
+        }
+        self.respond(&id, Value::Null);
+    }
+
+    fn open(&self, params: &Value) -> Option<String> {
+        let document = &params["textDocument"];
+        let uri = document["uri"].as_str()?.to_owned();
+        let path = reqwest::Url::parse(&uri)
+            .ok()
+            .and_then(|u| u.to_file_path().ok());
+        self.documents.lock().unwrap().insert(
+            uri.clone(),
+            Document {
+                text: document["text"].as_str()?.to_owned(),
+                version: document["version"].as_i64().unwrap_or_default(),
+                format: format(
+                    document["languageId"].as_str().unwrap_or_default(),
+                    path.as_deref(),
+                ),
+                path,
+            },
+        );
+        Some(uri)
+    }
+
+    /// Full synchronization: the last change contains the whole text
+    fn change(&self, params: &Value) -> Option<String> {
+        let uri = params["textDocument"]["uri"].as_str()?.to_owned();
+        let text = params["contentChanges"].as_array()?.last()?["text"].as_str()?;
+        let mut documents = self.documents.lock().unwrap();
+        let document = documents.get_mut(&uri)?;
+        document.text = text.to_owned();
+        document.version = params["textDocument"]["version"]
+            .as_i64()
+            .unwrap_or(document.version + 1);
+        Some(uri)
+    }
+
+    /// Handle a message, returning false on exit
+    fn handle(self: &Arc<Self>, message: Value) -> bool {
+        let id = &message["id"];
+        let params = &message["params"];
+        let method = message["method"].as_str().unwrap_or_default();
+        debug!("Received {}", method);
+        match method {
+            "initialize" => self.respond(
+                id,
+                json!({
+                    "capabilities": {
+                        "textDocumentSync": { "openClose": true, "change": 1 },
+                        "codeActionProvider": true,
+                        "executeCommandProvider": { "commands": [ADD_WORD, DISABLE_RULE] },
+                    },
+                    "serverInfo": { "name": "ltapi-client", "version": env!("CARGO_PKG_VERSION") },
+                }),
+            ),
+            "shutdown" => self.respond(id, Value::Null),
+            "exit" => return false,
+            "textDocument/didOpen" => {
+                if let Some(uri) = self.open(params) {
+                    tokio::spawn(self.clone().check(uri));
+                }
+            }
+            "textDocument/didChange" => {
+                if let Some(uri) = self.change(params) {
+                    tokio::spawn(self.clone().check(uri));
+                }
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                    self.documents.lock().unwrap().remove(uri);
+                    self.notify(
+                        "textDocument/publishDiagnostics",
+                        json!({ "uri": uri, "diagnostics": [] }),
+                    );
+                }
+            }
+            "textDocument/codeAction" => {
+                self.respond(id, code_actions(self.args.suggestions, params).into())
+            }
+            "workspace/executeCommand" => {
+                tokio::spawn(self.clone().execute_command(id.clone(), params.clone()));
+            }
+            // Other requests are not supported, other notifications ignored
+            _ if !id.is_null() => self.send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("Unsupported method {}", method) },
+            })),
+            _ => {}
+        }
+        true
+    }
+}
+
+/// Read a message, with its `Content-Length` header
+async fn read(
+    reader: &mut (impl tokio::io::AsyncBufRead + Unpin),
+) -> anyhow::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse()?);
+        }
+    }
+    let mut body = vec![0; length.context("Missing Content-Length header")?];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub async fn run(args: Flags, backend: Backend) -> anyhow::Result<()> {
+    let (out, mut rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let body = message.to_string();
+            stdout
+                .write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+                .await?;
+            stdout.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+    let server = Arc::new(Server {
+        args,
+        backend,
+        out,
+        documents: Default::default(),
+        disabled_rules: Default::default(),
+    });
+    info!("Language server started on stdio");
+    let mut stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    while let Some(message) = read(&mut stdin).await? {
+        if !server.handle(message) {
+            break;
+        }
+    }
+    drop(server);
+    // Pending checks and responses are abandoned on exit
+    writer.abort();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replacement_titles() {
+        let params = json!({
+            "textDocument": { "uri": "file:///a.md" },
+            "context": { "diagnostics": [{
+                "source": "ltapi",
+                "range": {},
+                "data": { "replacements": ["This"], "word": "thiss", "rule": "" },
+            }] },
+        });
+        let titles: Vec<_> = code_actions(3, &params)
+            .into_iter()
+            .map(|a| a["title"].clone())
+            .collect();
+        assert_eq!(
+            titles,
+            ["Replace with This", "Add \"thiss\" to the dictionary"]
+        );
+    }
+}
@@ -0,0 +1,551 @@
+use std::path::{Path, PathBuf};
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use futures::{StreamExt, TryStreamExt};
+use log::*;
+
+use ltapiserv_rs::analysis;
+use ltapiserv_rs::api::{Annotations, Request, Response};
+use ltapiserv_rs::formats::{self, Format};
+use ltapiserv_rs::stats::Stats;
+
+use backend::Backend;
+
+mod backend;
+mod baseline;
+mod config;
+mod diff;
+mod fix;
+mod inputs;
+mod lsp;
+mod report;
+mod suppress;
+mod watch;
+
+/// Run text through a LanguageTool server and display the results.
+#[derive(Parser)]
+struct Flags {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// Files, directories or globs; if not provided, will read from stdin (or use the `include`
+    /// globs of the configuration). Directories are searched for text, Markdown, LaTeX, HTML and
+    /// XML documents, respecting `.gitignore`.
+    filenames: Vec<PathBuf>,
+    /// Language, by default from the configuration or en-US
+    #[clap(long, short, global = true)]
+    language: Option<String>,
+    /// Server base URL (e.g. http://localhost:8875)
+    #[clap(long, short, env = "LTAPI_SERVER", required_unless_present = "archive")]
+    server: Option<reqwest::Url>,
+    /// Path to a .tar.gz data archive: check in-process with the checkers of the archive instead
+    /// of using a server. Takes precedence over `--server`.
+    #[clap(long, env = "LTAPI_ARCHIVE")]
+    archive: Option<PathBuf>,
+    /// JSON output; with several files, an object with the results of each file
+    #[clap(long, global = true)]
+    json: bool,
+    /// Output format
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with = "json",
+        global = true
+    )]
+    format: report::OutputFormat,
+    /// Number of suggestions to display
+    #[clap(long, default_value_t = 3, global = true)]
+    suggestions: usize,
+    /// Format of the input, by default guessed from the file extension (.md, .tex, .html, .xml,
+    /// or source code such as .rs, .py, .js). Markup and code are removed before checking,
+    /// preserving the positions of the errors.
+    #[clap(long, value_enum)]
+    input_format: Option<Format>,
+    /// Also check the string literals of source code, besides comments and docstrings
+    #[clap(long)]
+    strings: bool,
+    /// Display readability statistics instead of checking the text
+    #[clap(long)]
+    stats: bool,
+    /// Apply the first suggestion of each match and write the files back
+    #[clap(long, conflicts_with_all = ["stats", "interactive"])]
+    fix: bool,
+    /// Review the matches one by one, accepting or choosing a suggestion, or adding the word to
+    /// the dictionary, and write the files back
+    #[clap(long, requires = "filenames", conflicts_with = "stats")]
+    interactive: bool,
+    /// With --fix or --interactive, print a unified diff instead of writing the files
+    #[clap(long)]
+    dry_run: bool,
+    /// Only report the matches that are not recorded in this baseline file
+    #[clap(long)]
+    baseline: Option<PathBuf>,
+    /// Record all the current matches in the baseline file instead of reporting them
+    #[clap(long, requires = "baseline", conflicts_with_all = ["stats", "fix", "interactive"])]
+    update_baseline: bool,
+    /// Only report the matches on the lines changed since a revision (e.g. `main` or
+    /// `origin/main...HEAD`), according to `git diff`
+    #[clap(long, value_name = "REV", conflicts_with_all = ["stats", "update_baseline"])]
+    diff: Option<String>,
+    /// Only report the matches on the staged lines, checking the content of the index
+    #[clap(long, conflicts_with_all = ["stats", "update_baseline", "fix", "interactive"])]
+    staged: bool,
+    /// Check the files again whenever they change, redrawing the reports
+    #[clap(long, conflicts_with_all = ["stats", "fix", "interactive", "update_baseline"])]
+    watch: bool,
+    /// Maximum number of characters per request; longer texts are split at paragraph or sentence
+    /// boundaries. Should not exceed the `--max-query-size` of the server.
+    #[clap(long, default_value_t = 50_000)]
+    max_query_size: usize,
+    /// Timeout of the requests to the server, in seconds
+    #[clap(long, default_value_t = 60)]
+    timeout: u64,
+    /// Maximum number of concurrent requests
+    #[clap(long, short, default_value_t = 4)]
+    jobs: usize,
+    /// Configuration from the `.ltapi.toml` file
+    #[clap(skip)]
+    config: config::Config,
+    /// Changed lines, with `--diff` or `--staged`
+    #[clap(skip)]
+    changes: Option<diff::Changes>,
+}
+
+impl Flags {
+    /// Language of a file, from the arguments or the configuration
+    fn language(&self, filename: Option<&Path>) -> anyhow::Result<String> {
+        Ok(match &self.language {
+            Some(language) => language.clone(),
+            None => self
+                .config
+                .language(filename)?
+                .unwrap_or_else(|| "en-US".into()),
+        })
+    }
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Display the tokens, lemmas, part-of-speech and chunk tags of the text, to debug rules.
+    Analyze {
+        /// Filename; if not provided, will read from stdin.
+        filename: Option<PathBuf>,
+    },
+    /// Language server over stdio, publishing the matches of the open documents as diagnostics
+    Lsp,
+    /// Check a git commit message, e.g. from a `commit-msg` hook. Comments and trailers are
+    /// skipped, as well as issue references and code-like tokens.
+    CommitMsg {
+        /// Commit message file, e.g. `.git/COMMIT_EDITMSG`
+        filename: PathBuf,
+        /// Minimum severity of the matches that make the check fail
+        #[clap(long, value_enum, default_value_t = report::Severity::Warning)]
+        fail_level: report::Severity,
+    },
+}
+
+/// Format of an input, from the arguments or its extension
+fn format(args: &Flags, filename: Option<&Path>) -> Format {
+    args.input_format
+        .or_else(|| filename.and_then(Format::from_path))
+        .unwrap_or_default()
+}
+
+/// Convert the markup or code of a text in a given format
+fn annotations(args: &Flags, format: Format, text: &str) -> Annotations {
+    match format.syntax() {
+        Some(syntax) => {
+            debug!("Extracting comments from {:?} code", format);
+            formats::code::to_annotations(text, syntax, args.strings)
+        }
+        None => {
+            if format != Format::Text {
+                debug!("Removing {:?} markup", format);
+            }
+            format.to_annotations(text)
+        }
+    }
+}
+
+/// Build the request for the annotations of a text in a given format, sent as plain text if
+/// there is no markup
+fn request(args: &Flags, format: Format, annotations: &Annotations, language: String) -> Request {
+    let request = if format == Format::Text {
+        Request::new(annotations.source(), language)
+    } else {
+        Request::from_annotations(annotations, language)
+    };
+    request.with_disabled_rules(&args.config.disabled_rules)
+}
+
+/// Check a text, split into chunks below the maximum query size that are sent concurrently
+async fn check_text(
+    args: &Flags,
+    backend: &Backend,
+    format: Format,
+    text: &str,
+    language: String,
+) -> anyhow::Result<Response> {
+    let annotations = annotations(args, format, text);
+    if annotations.text_len() <= args.max_query_size {
+        return backend
+            .check(&request(args, format, &annotations, language))
+            .await;
+    }
+    let chunks = annotations.split(args.max_query_size);
+    debug!("Splitting the text into {} chunks", chunks.len());
+    let requests: Vec<Request> = chunks
+        .iter()
+        .map(|(_, chunk)| request(args, format, chunk, language.clone()))
+        .collect();
+    let responses: Vec<Response> = futures::stream::iter(requests)
+        .map(|request| async move { backend.check(&request).await })
+        .buffered(args.jobs.max(1))
+        .try_collect()
+        .await?;
+    // Offsets relative to the whole text
+    let mut matches = vec![];
+    let mut language = None;
+    for ((offset, _), response) in chunks.iter().zip(responses) {
+        language.get_or_insert(response.language);
+        matches.extend(response.matches.into_iter().map(|mut m| {
+            m.offset += offset;
+            m
+        }));
+    }
+    Ok(Response {
+        matches,
+        language: language.unwrap(),
+    })
+}
+
+/// Read an input, from the index with `--staged`
+fn read(args: &Flags, filename: Option<&Path>) -> anyhow::Result<String> {
+    let text = match (&args.changes, filename) {
+        (Some(changes), Some(filename)) => changes.read(filename)?,
+        _ => inputs::read(filename)?,
+    };
+    debug!("Text to process: {}", text);
+    Ok(text)
+}
+
+/// Read an input and compute its readability statistics, returning the text and the statistics
+async fn stats(
+    args: &Flags,
+    backend: &Backend,
+    filename: Option<&Path>,
+) -> anyhow::Result<(String, Stats)> {
+    let text = read(args, filename)?;
+    debug!("Computing statistics for {}", inputs::name(filename));
+    let start = std::time::Instant::now();
+    let format = format(args, filename);
+    let request = request(
+        args,
+        format,
+        &annotations(args, format, &text),
+        args.language(filename)?,
+    );
+    let response = backend.stats(&request).await?;
+    debug!("Received response in {:?}", start.elapsed());
+    Ok((text, response))
+}
+
+/// Read an input and check it, returning the text and the response
+async fn check(
+    args: &Flags,
+    backend: &Backend,
+    filename: Option<&Path>,
+) -> anyhow::Result<(String, Response)> {
+    let text = read(args, filename)?;
+    debug!("Checking {}", inputs::name(filename));
+    let start = std::time::Instant::now();
+    let language = args.language(filename)?;
+    let response = check_text(args, backend, format(args, filename), &text, language).await?;
+    debug!("Received response in {:?}", start.elapsed());
+    Ok((text, response))
+}
+
+/// Process all the inputs concurrently, in order
+async fn process_all<'a, T, F>(
+    args: &Flags,
+    filenames: &'a [Option<PathBuf>],
+    process: impl Fn(Option<&'a Path>) -> F,
+) -> Vec<anyhow::Result<(String, T)>>
+where
+    F: std::future::Future<Output = anyhow::Result<(String, T)>>,
+{
+    futures::stream::iter(filenames)
+        .map(|f| process(f.as_deref()))
+        .buffered(args.jobs.max(1))
+        .collect()
+        .await
+}
+
+/// Remove the matches suppressed by directives or the configuration, those on unchanged lines
+/// and those recorded in the baseline, returning the number of the latter
+fn filter(
+    args: &Flags,
+    baseline: Option<&baseline::Baseline>,
+    file: &mut report::Checked,
+) -> usize {
+    let n_suppressed = suppress::Suppressions::parse(&file.text).filter(file);
+    let n_suppressed = n_suppressed + args.config.filter(file);
+    if n_suppressed > 0 {
+        debug!(
+            "{}: {} matches suppressed by directives",
+            file.name, n_suppressed
+        );
+    }
+    if let Some(changes) = &args.changes {
+        let n_unchanged = changes.filter(file);
+        debug!("{}: {} matches on unchanged lines", file.name, n_unchanged);
+    }
+    baseline.map_or(0, |b| b.filter(file))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    if let Err(err) = main_impl().await {
+        error!("{:#}", err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+async fn main_impl() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .filter_module("nlprule", LevelFilter::Error)
+        .init();
+
+    let config = config::Config::discover()?;
+    let command = match &config {
+        Some(config) => config.defaults(Flags::command()),
+        None => Flags::command(),
+    };
+    let mut args = Flags::from_arg_matches(&command.get_matches())?;
+    args.config = config.unwrap_or_default();
+
+    let backend = Backend::new(&args).await?;
+
+    if matches!(args.command, Some(Command::Lsp)) {
+        return lsp::run(args, backend).await;
+    }
+
+    if let Some(Command::Analyze { filename }) = &args.command {
+        let text = inputs::read(filename.as_deref())?;
+        let sentences: Vec<analysis::Sentence> = backend
+            .analyze(&Request::new(text, args.language(filename.as_deref())?))
+            .await?;
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&sentences)?);
+        } else {
+            for (i, sentence) in sentences.iter().enumerate() {
+                println!(
+                    "Sentence {} ({}..{}): {}\n",
+                    i + 1,
+                    sentence.offset,
+                    sentence.offset + sentence.length,
+                    sentence.text.trim()
+                );
+                println!("{}\n", sentence.table());
+            }
+        }
+        return Ok(());
+    }
+
+    // Matches at or above this severity make the check fail
+    let mut fail_level = report::Severity::Advice;
+    if let Some(Command::CommitMsg {
+        filename,
+        fail_level: level,
+    }) = &args.command
+    {
+        fail_level = *level;
+        args.filenames = vec![filename.clone()];
+        args.input_format.get_or_insert(Format::Commit);
+    }
+
+    let paths = if args.filenames.is_empty() {
+        args.config.include()
+    } else {
+        args.filenames.clone()
+    };
+    if args.diff.is_some() || args.staged {
+        args.changes = Some(diff::Changes::from_git(args.diff.as_deref(), args.staged)?);
+    }
+    let filenames: Vec<Option<PathBuf>> = if let Some(changes) = &args.changes {
+        // Changed files among the given ones, or all the changed files that can be checked
+        let files = if paths.is_empty() {
+            changes
+                .files()
+                .into_iter()
+                .filter(|f| inputs::is_supported(f))
+                .collect()
+        } else {
+            inputs::collect(&paths)?
+                .into_iter()
+                .filter(|f| changes.contains(f))
+                .collect()
+        };
+        let files = args.config.exclude(files)?;
+        if files.is_empty() {
+            info!("No changed files to check");
+            return Ok(());
+        }
+        files.into_iter().map(Some).collect()
+    } else if paths.is_empty() {
+        vec![None]
+    } else {
+        args.config
+            .exclude(inputs::collect(&paths)?)?
+            .into_iter()
+            .map(Some)
+            .collect()
+    };
+    let several = filenames.len() > 1;
+    let mut n_failed = 0;
+
+    if args.stats {
+        let results: Vec<anyhow::Result<(String, Stats)>> =
+            process_all(&args, &filenames, |f| stats(&args, &backend, f)).await;
+        let mut values = vec![];
+        for (filename, result) in filenames.iter().zip(&results) {
+            match result {
+                Ok((_, stats)) => values.push((inputs::name(filename.as_deref()), stats)),
+                Err(e) => {
+                    error!("{}: {:#}", inputs::name(filename.as_deref()), e);
+                    n_failed += 1;
+                }
+            }
+        }
+        if args.json {
+            report::print_json(&values, several)?;
+        } else {
+            for (name, stats) in values {
+                if several {
+                    println!("{}:", name);
+                }
+                println!("{}", stats);
+            }
+        }
+        if n_failed > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let format = if args.json {
+        report::OutputFormat::Json
+    } else {
+        args.format
+    };
+    let baseline = match &args.baseline {
+        Some(path) if !args.update_baseline => Some(baseline::Baseline::load(path)?),
+        _ => None,
+    };
+
+    if args.watch {
+        let files: Vec<PathBuf> = filenames.into_iter().flatten().collect();
+        if files.is_empty() {
+            anyhow::bail!("No files to watch");
+        }
+        return watch::watch(&args, &backend, format, baseline.as_ref(), &files).await;
+    }
+
+    info!("Checking {} file(s)", filenames.len());
+    let start = std::time::Instant::now();
+    let results: Vec<anyhow::Result<(String, Response)>> =
+        process_all(&args, &filenames, |f| check(&args, &backend, f)).await;
+    info!("Received all responses in {:?}", start.elapsed());
+
+    let mut n_errors = 0;
+    let mut n_failing = 0;
+    let mut n_known = 0;
+    let mut n_files_with_errors = 0;
+    let mut checked = vec![];
+    for (filename, result) in filenames.iter().zip(results) {
+        let name = inputs::name(filename.as_deref());
+        let (text, response) = match result {
+            Ok(r) => r,
+            Err(e) => {
+                error!("{}: {:#}", name, e);
+                n_failed += 1;
+                continue;
+            }
+        };
+        let mut file = report::Checked {
+            path: filename.clone(),
+            name,
+            text,
+            response,
+        };
+        n_known += filter(&args, baseline.as_ref(), &mut file);
+        if args.update_baseline {
+            checked.push(file);
+            continue;
+        }
+        let n_matches = file.response.matches.len();
+        n_errors += n_matches;
+        n_failing += file
+            .response
+            .matches
+            .iter()
+            .filter(|m| report::Severity::of(m) >= fail_level)
+            .count();
+        n_files_with_errors += usize::from(n_matches > 0);
+        if n_matches == 0 {
+            info!("{}: no errors found", file.name);
+        } else {
+            info!("{}: {} potential errors", file.name, n_matches);
+        }
+        if args.fix || args.interactive {
+            checked.push(file);
+        } else if format == report::OutputFormat::Pretty {
+            report::pretty(&file, args.suggestions);
+        } else {
+            checked.push(file);
+        }
+    }
+    if let (Some(path), true) = (&args.baseline, args.update_baseline) {
+        let baseline = baseline::Baseline::new(&checked);
+        baseline.save(path)?;
+        info!("Recorded {} matches in {:?}", baseline.len(), path);
+        if n_failed > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if n_known > 0 {
+        info!("Ignored {} potential errors from the baseline", n_known);
+    }
+    if args.fix || args.interactive {
+        let remaining = if args.fix {
+            fix::fix(&args, &checked)?
+        } else {
+            fix::interactive(&args, &backend, &checked).await?
+        };
+        info!("{} of {} potential errors left", remaining, n_errors);
+        if remaining > 0 || n_failed > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    report::print(format, &checked, args.suggestions, several)?;
+    if several {
+        info!(
+            "Found {} potential errors in {} of {} files",
+            n_errors,
+            n_files_with_errors,
+            filenames.len()
+        );
+    } else {
+        info!("Found {} potential errors", n_errors);
+    }
+    if n_failed > 0 {
+        error!("Failed to check {} file(s)", n_failed);
+    }
+    if n_failing > 0 || n_failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
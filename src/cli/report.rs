@@ -0,0 +1,706 @@
+/// Output of the results: graphical reports, or machine-readable formats for CI systems, with
+/// the line and column of each match.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use serde_json::json;
+
+use ltapiserv_rs::api::{Match, Response};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Graphical reports
+    #[default]
+    Pretty,
+    /// One line per match, `file:line:col: severity: message [RULE_ID] (suggestions)`, e.g.
+    /// for Vim's quickfix or Emacs' compilation mode
+    Compact,
+    /// Responses of the server
+    Json,
+    /// SARIF 2.1.0, e.g. for GitHub code scanning
+    Sarif,
+    /// JUnit XML, e.g. for Jenkins
+    Junit,
+    /// Checkstyle XML
+    Checkstyle,
+    /// GitHub Actions workflow commands, displayed as annotations
+    Github,
+    /// GitLab Code Quality report
+    GitlabCodequality,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Grammar, style and typography
+    Advice,
+    /// Spelling
+    Warning,
+}
+
+impl Severity {
+    pub fn of(m: &Match) -> Self {
+        if m.rule.is_spelling() {
+            Severity::Warning
+        } else {
+            Severity::Advice
+        }
+    }
+    /// Name of the severity, as in SARIF levels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Advice => "note",
+        }
+    }
+}
+
+/// Line and column (1-based, in characters), byte offset and LSP position of character offsets
+pub struct SourceIndex {
+    /// Character offset of the start of each line
+    line_starts: Vec<usize>,
+    /// Byte offset of each character, and of the end of the text
+    bytes: Vec<usize>,
+}
+
+impl SourceIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut bytes = Vec::with_capacity(text.len() + 1);
+        for (i, (byte, c)) in text.char_indices().enumerate() {
+            bytes.push(byte);
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        bytes.push(text.len());
+        Self { line_starts, bytes }
+    }
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        (line, offset - self.line_starts[line - 1] + 1)
+    }
+    /// Byte offset of a character offset, clamped to the end of the text
+    pub fn byte_offset(&self, offset: usize) -> usize {
+        self.bytes[offset.min(self.bytes.len() - 1)]
+    }
+    /// Line (0-based) and column (in UTF-16 code units) of a character offset of `text`, clamped
+    /// to its end, as in the Language Server Protocol
+    pub fn utf16_position(&self, text: &str, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.bytes.len() - 1);
+        let (line, _) = self.position(offset);
+        let start = self.byte_offset(self.line_starts[line - 1]);
+        let column = text[start..self.byte_offset(offset)].encode_utf16().count();
+        (line - 1, column)
+    }
+}
+
+/// Results of the check of a file
+pub struct Checked {
+    /// Path, or `None` for stdin
+    pub path: Option<PathBuf>,
+    pub name: String,
+    pub text: String,
+    pub response: Response,
+}
+
+/// Match with its start and end positions
+struct Located<'a> {
+    m: &'a Match,
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+impl Checked {
+    fn located(&self) -> Vec<Located<'_>> {
+        let index = SourceIndex::new(&self.text);
+        self.response
+            .matches
+            .iter()
+            .map(|m| Located {
+                m,
+                start: index.position(m.offset),
+                end: index.position(m.offset + m.length),
+            })
+            .collect()
+    }
+}
+
+/// Rule ID, or the issue type for the checks without one
+pub fn rule_id(m: &Match) -> String {
+    if m.rule.id.is_empty() {
+        m.rule.issue_type().to_uppercase()
+    } else {
+        m.rule.id.clone()
+    }
+}
+
+/// First suggestions of a match, separated by slashes
+fn suggestions_label(m: &Match, suggestions: usize) -> String {
+    m.replacements
+        .iter()
+        .take(suggestions)
+        .map(|r| &r.value)
+        .join(" / ")
+}
+
+/// Message with the first suggestions
+fn message(m: &Match, suggestions: usize) -> String {
+    let replacements = suggestions_label(m, suggestions);
+    if replacements.is_empty() {
+        m.message.clone()
+    } else {
+        format!("{} ({})", m.message, replacements)
+    }
+}
+
+/// Text covered by a match
+pub fn matched_text(text: &str, m: &Match) -> String {
+    text.chars().skip(m.offset).take(m.length).collect()
+}
+
+/// Number of characters around a match included in its fingerprint
+const FINGERPRINT_CONTEXT: usize = 16;
+
+/// Stable identifier of a match, from its rule, text and context on the same line, which does
+/// not depend on its position in the file.
+pub fn fingerprint(name: &str, text: &str, m: &Match) -> String {
+    let start = m.offset.saturating_sub(FINGERPRINT_CONTEXT);
+    let before: String = text.chars().skip(start).take(m.offset - start).collect();
+    let before = before.rsplit('\n').next().unwrap_or_default();
+    let after: String = text
+        .chars()
+        .skip(m.offset + m.length)
+        .take_while(|c| *c != '\n')
+        .take(FINGERPRINT_CONTEXT)
+        .collect();
+    let mut hasher = blake3::Hasher::new();
+    for part in [name, &rule_id(m), &matched_text(text, m), before, &after] {
+        // Normalize whitespace, which can change with reformatting
+        hasher.update(part.split_whitespace().join(" ").as_bytes());
+        hasher.update(&[0]);
+    }
+    hasher.finalize().to_hex()[..32].to_string()
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Print one JSON value, or an object with the value for each file if there are several
+pub fn print_json<T: serde::Serialize>(
+    values: &[(String, T)],
+    several: bool,
+) -> anyhow::Result<()> {
+    if let ([(_, value)], false) = (values, several) {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        return Ok(());
+    }
+    let object: serde_json::Map<String, serde_json::Value> = values
+        .iter()
+        .map(|(name, v)| Ok((name.clone(), serde_json::to_value(v)?)))
+        .collect::<anyhow::Result<_>>()?;
+    println!("{}", serde_json::to_string_pretty(&object)?);
+    Ok(())
+}
+
+/// Source code of a file for graphical reports
+pub fn named_source(checked: &Checked) -> Arc<miette::NamedSource<String>> {
+    Arc::new(miette::NamedSource::new(
+        &checked.name,
+        checked.text.clone(),
+    ))
+}
+
+fn miette_severity(severity: Severity) -> miette::Severity {
+    match severity {
+        Severity::Warning => miette::Severity::Warning,
+        Severity::Advice => miette::Severity::Advice,
+    }
+}
+
+/// Graphical report of a match, or of the matches on a line with a report for each one as
+/// related diagnostics
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+struct Report {
+    message: String,
+    code: Option<String>,
+    url: Option<String>,
+    severity: Severity,
+    labels: Vec<miette::LabeledSpan>,
+    source_code: Option<Arc<miette::NamedSource<String>>>,
+    related: Vec<Report>,
+}
+
+impl Report {
+    fn new(m: &Match) -> Self {
+        Self {
+            message: m.message.clone(),
+            code: Some(rule_id(m)),
+            url: m.rule.urls().next().map(String::from),
+            severity: Severity::of(m),
+            labels: vec![],
+            source_code: None,
+            related: vec![],
+        }
+    }
+}
+
+impl miette::Diagnostic for Report {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.code
+            .as_ref()
+            .map(|c| Box::new(c) as Box<dyn std::fmt::Display>)
+    }
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette_severity(self.severity))
+    }
+    fn url<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.url
+            .as_ref()
+            .map(|u| Box::new(u) as Box<dyn std::fmt::Display>)
+    }
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.source_code
+            .as_ref()
+            .map(|s| s.as_ref() as &dyn miette::SourceCode)
+    }
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        Some(Box::new(self.labels.iter().cloned()))
+    }
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn miette::Diagnostic> + 'a>> {
+        Some(Box::new(
+            self.related.iter().map(|r| r as &dyn miette::Diagnostic),
+        ))
+    }
+}
+
+/// Span of a match for miette, in bytes
+fn span(index: &SourceIndex, m: &Match) -> std::ops::Range<usize> {
+    index.byte_offset(m.offset)..index.byte_offset(m.offset + m.length)
+}
+
+/// Graphical report for a match
+fn match_report(
+    source: &Arc<miette::NamedSource<String>>,
+    index: &SourceIndex,
+    m: &Match,
+    suggestions: usize,
+) -> Report {
+    Report {
+        labels: vec![miette::LabeledSpan::at(
+            span(index, m),
+            suggestions_label(m, suggestions),
+        )],
+        source_code: Some(source.clone()),
+        ..Report::new(m)
+    }
+}
+
+/// Print a graphical report for a match
+pub fn pretty_match(
+    source: &Arc<miette::NamedSource<String>>,
+    index: &SourceIndex,
+    m: &Match,
+    suggestions: usize,
+) {
+    let report = match_report(source, index, m, suggestions);
+    println!("{:?}", miette::Report::new(report));
+}
+
+/// Graphical report for the matches sharing a line, labelled with their rule IDs, and detailed
+/// as related reports
+fn line_report(
+    source: &Arc<miette::NamedSource<String>>,
+    index: &SourceIndex,
+    line: usize,
+    matches: &[&Match],
+    suggestions: usize,
+) -> Report {
+    Report {
+        message: format!("{} issues on line {}", matches.len(), line),
+        code: None,
+        url: None,
+        severity: matches.iter().map(|m| Severity::of(m)).max().unwrap(),
+        labels: matches
+            .iter()
+            .map(|m| {
+                let label = suggestions_label(m, suggestions);
+                miette::LabeledSpan::at(
+                    span(index, m),
+                    if label.is_empty() {
+                        rule_id(m)
+                    } else {
+                        format!("{}: {}", rule_id(m), label)
+                    },
+                )
+            })
+            .collect(),
+        source_code: Some(source.clone()),
+        related: matches.iter().map(|m| Report::new(m)).collect(),
+    }
+}
+
+/// Print graphical reports for the matches of a file, one per line. The matches sharing a line
+/// are labelled with their rule IDs, and detailed below the source.
+pub fn pretty(checked: &Checked, suggestions: usize) {
+    let source = named_source(checked);
+    let index = SourceIndex::new(&checked.text);
+    let lines = checked
+        .response
+        .matches
+        .iter()
+        .sorted_by_key(|m| m.offset)
+        .chunk_by(|m| index.position(m.offset).0);
+    for (line, matches) in &lines {
+        let matches: Vec<&Match> = matches.collect();
+        if let [m] = matches[..] {
+            pretty_match(&source, &index, m, suggestions);
+            continue;
+        }
+        let report = line_report(&source, &index, line, &matches, suggestions);
+        println!("{:?}", miette::Report::new(report));
+    }
+}
+
+fn compact(results: &[Checked], suggestions: usize) -> String {
+    results
+        .iter()
+        .flat_map(|c| c.located().into_iter().map(move |l| (c, l)))
+        .map(|(c, l)| {
+            let replacements = suggestions_label(l.m, suggestions);
+            format!(
+                "{}:{}:{}: {}: {} [{}]{}",
+                c.name,
+                l.start.0,
+                l.start.1,
+                Severity::of(l.m).as_str(),
+                l.m.message.replace('\n', " "),
+                rule_id(l.m),
+                if replacements.is_empty() {
+                    "".into()
+                } else {
+                    format!(" ({})", replacements)
+                }
+            )
+        })
+        .join("\n")
+}
+
+/// Percent-encoded `file://` URI of a path, resolving relative paths against the working directory
+fn file_uri(name: &str) -> String {
+    std::path::absolute(name)
+        .ok()
+        .and_then(|path| reqwest::Url::from_file_path(path).ok())
+        .map_or_else(|| name.into(), String::from)
+}
+
+fn sarif(results: &[Checked], suggestions: usize) -> serde_json::Value {
+    let rules: Vec<_> = results
+        .iter()
+        .flat_map(|c| &c.response.matches)
+        .unique_by(|m| rule_id(m))
+        .map(|m| {
+            json!({
+                "id": rule_id(m),
+                "shortDescription": {"text": m.rule.description()},
+            })
+        })
+        .collect();
+    let sarif_results: Vec<_> = results
+        .iter()
+        .flat_map(|c| c.located().into_iter().map(move |l| (c, l)))
+        .map(|(c, l)| {
+            json!({
+                "ruleId": rule_id(l.m),
+                "level": Severity::of(l.m).as_str(),
+                "message": {"text": message(l.m, suggestions)},
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": file_uri(&c.name)},
+                        "region": {
+                            "startLine": l.start.0,
+                            "startColumn": l.start.1,
+                            "endLine": l.end.0,
+                            "endColumn": l.end.1,
+                        },
+                    },
+                }],
+                "partialFingerprints": {
+                    "ltapiFingerprint/v1": fingerprint(&c.name, &c.text, l.m),
+                },
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ltapi-client",
+                    "informationUri": "https://github.com/cpg314/ltapiserv-rs",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": sarif_results,
+        }],
+    })
+}
+
+/// One test suite per file, with a failing test case per match
+fn junit(results: &[Checked], suggestions: usize) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let failures: usize = results.iter().map(|c| c.response.matches.len()).sum();
+    out += &format!(
+        "<testsuites name=\"ltapi\" tests=\"{}\" failures=\"{}\">\n",
+        results
+            .iter()
+            .map(|c| c.response.matches.len().max(1))
+            .sum::<usize>(),
+        failures
+    );
+    for c in results {
+        let name = escape_xml(&c.name);
+        out += &format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            name,
+            c.response.matches.len().max(1),
+            c.response.matches.len()
+        );
+        let located = c.located();
+        if located.is_empty() {
+            out += &format!("    <testcase name=\"{}\" classname=\"ltapi\"/>\n", name);
+        }
+        for l in located {
+            out += &format!(
+                "    <testcase name=\"{}:{}:{} {}\" classname=\"ltapi\">\n      \
+                 <failure message=\"{}\" type=\"{}\">{}:{}:{}: {}</failure>\n    </testcase>\n",
+                name,
+                l.start.0,
+                l.start.1,
+                escape_xml(&rule_id(l.m)),
+                escape_xml(&l.m.message),
+                escape_xml(&rule_id(l.m)),
+                name,
+                l.start.0,
+                l.start.1,
+                escape_xml(&message(l.m, suggestions))
+            );
+        }
+        out += "  </testsuite>\n";
+    }
+    out += "</testsuites>";
+    out
+}
+
+fn checkstyle(results: &[Checked], suggestions: usize) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out += "<checkstyle version=\"4.3\">\n";
+    for c in results {
+        out += &format!("  <file name=\"{}\">\n", escape_xml(&c.name));
+        for l in c.located() {
+            out += &format!(
+                "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"ltapi.{}\"/>\n",
+                l.start.0,
+                l.start.1,
+                match Severity::of(l.m) {
+                    Severity::Advice => "info",
+                    severity => severity.as_str(),
+                },
+                escape_xml(&message(l.m, suggestions)),
+                escape_xml(&rule_id(l.m))
+            );
+        }
+        out += "  </file>\n";
+    }
+    out += "</checkstyle>";
+    out
+}
+
+/// GitHub Actions workflow commands
+fn github(results: &[Checked], suggestions: usize) -> String {
+    let escape_data = |s: &str| {
+        s.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |s: &str| escape_data(s).replace(':', "%3A").replace(',', "%2C");
+    results
+        .iter()
+        .flat_map(|c| c.located().into_iter().map(move |l| (c, l)))
+        .map(|(c, l)| {
+            format!(
+                "::{} file={},line={},col={},endLine={},endColumn={},title={}::{}",
+                match Severity::of(l.m) {
+                    Severity::Advice => "notice",
+                    severity => severity.as_str(),
+                },
+                escape_property(&c.name),
+                l.start.0,
+                l.start.1,
+                l.end.0,
+                l.end.1,
+                escape_property(&rule_id(l.m)),
+                escape_data(&message(l.m, suggestions))
+            )
+        })
+        .join("\n")
+}
+
+fn gitlab_codequality(results: &[Checked], suggestions: usize) -> serde_json::Value {
+    results
+        .iter()
+        .flat_map(|c| c.located().into_iter().map(move |l| (c, l)))
+        .map(|(c, l)| {
+            json!({
+                "description": message(l.m, suggestions),
+                "check_name": rule_id(l.m),
+                "fingerprint": fingerprint(&c.name, &c.text, l.m),
+                "severity": match Severity::of(l.m) {
+                    Severity::Warning => "minor",
+                    Severity::Advice => "info",
+                },
+                "location": {
+                    "path": c.name,
+                    "lines": {"begin": l.start.0, "end": l.end.0},
+                },
+            })
+        })
+        .collect()
+}
+
+/// Print the results of all files in a machine-readable format
+pub fn print(
+    format: OutputFormat,
+    results: &[Checked],
+    suggestions: usize,
+    several: bool,
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Pretty => {
+            for checked in results {
+                pretty(checked, suggestions);
+            }
+        }
+        OutputFormat::Json => {
+            let values: Vec<_> = results
+                .iter()
+                .map(|c| (c.name.clone(), &c.response))
+                .collect();
+            print_json(&values, several)?;
+        }
+        OutputFormat::Sarif => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&sarif(results, suggestions))?
+            )
+        }
+        OutputFormat::Junit => println!("{}", junit(results, suggestions)),
+        OutputFormat::Checkstyle => println!("{}", checkstyle(results, suggestions)),
+        OutputFormat::Compact | OutputFormat::Github => {
+            let lines = if format == OutputFormat::Compact {
+                compact(results, suggestions)
+            } else {
+                github(results, suggestions)
+            };
+            if !lines.is_empty() {
+                println!("{}", lines);
+            }
+        }
+        OutputFormat::GitlabCodequality => println!(
+            "{}",
+            serde_json::to_string_pretty(&gitlab_codequality(results, suggestions))?
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn source_index() {
+        let index = SourceIndex::new("ab\ncé\n\nd");
+        assert_eq!(index.position(0), (1, 1));
+        assert_eq!(index.position(2), (1, 3));
+        assert_eq!(index.position(3), (2, 1));
+        assert_eq!(index.position(5), (2, 3));
+        assert_eq!(index.position(7), (4, 1));
+        assert_eq!(index.position(8), (4, 2));
+        assert_eq!(index.byte_offset(5), 6);
+        // End of the text, and beyond for inconsistent responses
+        assert_eq!(index.byte_offset(8), 9);
+        assert_eq!(index.byte_offset(12), 9);
+    }
+
+    #[test]
+    fn utf16_positions() {
+        let text = "a😀b\nc";
+        let index = SourceIndex::new(text);
+        assert_eq!(index.utf16_position(text, 2), (0, 3));
+        assert_eq!(index.utf16_position(text, 4), (1, 0));
+        assert_eq!(index.utf16_position(text, 5), (1, 1));
+        assert_eq!(index.utf16_position(text, 9), (1, 1));
+    }
+
+    #[test]
+    fn rule_links() {
+        let m = |offset| Match {
+            offset,
+            length: 4,
+            rule: ltapiserv_rs::api::Rule::from_id("GRAMMAR/A_AN".into())
+                .with_url(Some("https://example.com/a-an")),
+            ..Default::default()
+        };
+        let text = "Thiss is a apple.";
+        let source = Arc::new(miette::NamedSource::new("a.md", text.to_string()));
+        let index = SourceIndex::new(text);
+        let render = |report: Report| {
+            let mut out = String::new();
+            miette::GraphicalReportHandler::new_themed(miette::GraphicalTheme::unicode_nocolor())
+                .with_links(false)
+                .render_report(&mut out, &report)
+                .unwrap();
+            out
+        };
+        let single = render(match_report(&source, &index, &m(9), 0));
+        assert!(single.contains("https://example.com/a-an"), "{}", single);
+        let grouped = render(line_report(&source, &index, 1, &[&m(0), &m(9)], 0));
+        assert!(grouped.contains("https://example.com/a-an"), "{}", grouped);
+    }
+
+    #[test]
+    fn fingerprint_stability() {
+        let m = |offset| Match {
+            offset,
+            length: 5,
+            rule: ltapiserv_rs::api::Rule::spelling(),
+            ..Default::default()
+        };
+        let text = "Thiss is fine.";
+        let reference = fingerprint("a.md", text, &m(0));
+        // Moved to another line, with other lines around
+        assert_eq!(
+            fingerprint("a.md", "Intro.\n\nThiss is fine.\nMore.", &m(8)),
+            reference
+        );
+        assert_ne!(fingerprint("b.md", text, &m(0)), reference);
+        assert_ne!(fingerprint("a.md", "Thiss is wrong.", &m(0)), reference);
+    }
+
+    #[test]
+    fn file_uris() {
+        let uri = file_uri("docs/a b#1.md");
+        assert!(uri.starts_with("file:///"), "{}", uri);
+        assert!(uri.ends_with("/docs/a%20b%231.md"), "{}", uri);
+        assert_eq!(file_uri("/tmp/é.md"), "file:///tmp/%C3%A9.md");
+    }
+}
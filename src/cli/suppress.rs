@@ -0,0 +1,177 @@
+/// Inline directives silencing matches where they are written, in any comment syntax, e.g.
+/// `<!-- ltapi-disable RULE_ID -->` ... `<!-- ltapi-enable -->`, `% ltapi-disable-next-line` or
+/// `// ltapi-disable-line`.
+use super::report::{self, Checked};
+
+/// Span of characters where the given rules (or all if empty) are disabled
+#[derive(Debug, PartialEq)]
+struct Range {
+    start: usize,
+    end: usize,
+    rules: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    ranges: Vec<Range>,
+}
+
+/// Rule IDs following a directive, until the end of the comment
+fn rules(arguments: &str) -> Vec<String> {
+    let end = ["-->", "*/"]
+        .iter()
+        .filter_map(|e| arguments.find(e))
+        .min()
+        .unwrap_or(arguments.len());
+    arguments[..end]
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|r| !r.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+impl Suppressions {
+    pub fn parse(text: &str) -> Self {
+        let directive =
+            regex::Regex::new(r"ltapi-(disable-next-line|disable-line|disable|enable)\b").unwrap();
+        let mut ranges = vec![];
+        // Disabled rules, with the start of the range
+        let mut open: Vec<(usize, Vec<String>)> = vec![];
+        // Character offset of each line (with its line break) and of its end
+        let mut line_start = 0;
+        let lines: Vec<(usize, usize, &str)> = text
+            .split_inclusive('\n')
+            .map(|line| {
+                let start = line_start;
+                line_start += line.chars().count();
+                (start, line_start, line)
+            })
+            .collect();
+        for (i, (start, end, line)) in lines.iter().enumerate() {
+            let Some(captures) = directive.captures(line) else {
+                continue;
+            };
+            let m = captures.get(0).unwrap();
+            let rules = rules(&line[m.end()..]);
+            // The directive itself, e.g. in a checked comment
+            ranges.push(Range {
+                start: start + line[..m.start()].chars().count(),
+                end: start + line[..m.end()].chars().count(),
+                rules: vec![],
+            });
+            match &captures[1] {
+                "disable-next-line" => {
+                    if let Some((next_start, next_end, _)) = lines.get(i + 1) {
+                        ranges.push(Range {
+                            start: *next_start,
+                            end: *next_end,
+                            rules,
+                        });
+                    }
+                }
+                "disable-line" => ranges.push(Range {
+                    start: *start,
+                    end: *end,
+                    rules,
+                }),
+                "disable" => open.push((*start, rules)),
+                "enable" => {
+                    // A directive without rules is only closed by one without rules
+                    let (closed, still_open) = open.into_iter().partition(|(_, r)| {
+                        rules.is_empty() || (!r.is_empty() && r.iter().all(|r| rules.contains(r)))
+                    });
+                    open = still_open;
+                    ranges.extend(closed.into_iter().map(|(start, rules)| Range {
+                        start,
+                        end: *end,
+                        rules,
+                    }));
+                }
+                _ => unreachable!(),
+            }
+        }
+        ranges.extend(open.into_iter().map(|(start, rules)| Range {
+            start,
+            end: line_start,
+            rules,
+        }));
+        Self { ranges }
+    }
+
+    /// Remove the suppressed matches from the results of a file, returning their number
+    pub fn filter(&self, checked: &mut Checked) -> usize {
+        let before = checked.response.matches.len();
+        checked.response.matches.retain(|m| {
+            let rule = report::rule_id(m);
+            !self.ranges.iter().any(|r| {
+                (r.start..r.end).contains(&m.offset)
+                    && (r.rules.is_empty() || r.rules.iter().any(|p| rule.starts_with(p.as_str())))
+            })
+        });
+        before - checked.response.matches.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let text = "A\n<!-- ltapi-disable SPELLING, STYLE -->\nB\n<!-- ltapi-enable -->\n\
+                    % ltapi-disable-next-line\nC\nD // ltapi-disable-line X\n";
+        let ranges = Suppressions::parse(text).ranges;
+        let covered = |rules: &[&str]| -> Vec<&str> {
+            ranges
+                .iter()
+                .filter(|r| r.rules == rules)
+                .map(|r| text[r.start..r.end].trim_end())
+                .collect()
+        };
+        // All characters are ASCII, offsets can be used as byte offsets
+        assert_eq!(
+            covered(&["SPELLING", "STYLE"]),
+            ["<!-- ltapi-disable SPELLING, STYLE -->\nB\n<!-- ltapi-enable -->"]
+        );
+        assert_eq!(
+            covered(&[]),
+            [
+                "ltapi-disable",
+                "ltapi-enable",
+                "ltapi-disable-next-line",
+                "C",
+                "ltapi-disable-line"
+            ]
+        );
+        assert_eq!(covered(&["X"]), ["D // ltapi-disable-line X"]);
+    }
+
+    #[test]
+    fn enable_rule() {
+        let text = "ltapi-disable
+A
+ltapi-disable X
+B
+ltapi-enable X
+C
+ltapi-enable
+D
+";
+        let ranges = Suppressions::parse(text).ranges;
+        let covered = |rules: &[&str]| -> Vec<&str> {
+            ranges
+                .iter()
+                .map(|r| (&r.rules, text[r.start..r.end].trim_end()))
+                // Skip the ranges of the directives themselves
+                .filter(|(r, covered)| *r == rules && covered.contains('\n'))
+                .map(|(_, covered)| covered)
+                .collect()
+        };
+        assert_eq!(covered(&["X"]), ["ltapi-disable X\nB\nltapi-enable X"]);
+        // Not closed by `ltapi-enable X`
+        assert_eq!(
+            covered(&[]),
+            ["ltapi-disable\nA\nltapi-disable X\nB\nltapi-enable X\nC\nltapi-enable"]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+/// Watch mode: the files are checked again when they change, and the reports are redrawn.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use itertools::Itertools;
+use log::*;
+use notify_debouncer_mini::notify::RecursiveMode;
+
+use ltapiserv_rs::api::{Language, Response};
+
+use super::backend::Backend;
+use super::baseline::Baseline;
+use super::report::{self, Checked};
+use super::Flags;
+
+/// Results of the last check of each file, and the error if the last check failed
+struct Checks {
+    checked: Vec<Checked>,
+    errors: Vec<Option<anyhow::Error>>,
+}
+
+impl Checks {
+    fn update(&mut self, i: usize, result: anyhow::Result<Checked>) {
+        match result {
+            Ok(checked) => {
+                self.checked[i] = checked;
+                self.errors[i] = None;
+            }
+            Err(e) => self.errors[i] = Some(e),
+        }
+    }
+}
+
+/// Check files concurrently, filtering their matches
+async fn check(
+    args: &Flags,
+    backend: &Backend,
+    baseline: Option<&Baseline>,
+    filenames: &[PathBuf],
+) -> Vec<anyhow::Result<Checked>> {
+    let filenames: Vec<Option<PathBuf>> = filenames.iter().cloned().map(Some).collect();
+    let results: Vec<anyhow::Result<(String, Response)>> =
+        super::process_all(args, &filenames, |f| super::check(args, backend, f)).await;
+    filenames
+        .into_iter()
+        .zip(results)
+        .map(|(path, result)| {
+            let (text, response) = result?;
+            let mut file = Checked {
+                name: super::inputs::name(path.as_deref()),
+                path,
+                text,
+                response,
+            };
+            super::filter(args, baseline, &mut file);
+            Ok(file)
+        })
+        .collect()
+}
+
+/// Clear the terminal and print the reports of all files
+fn redraw(args: &Flags, format: report::OutputFormat, checks: &Checks) {
+    print!("\x1b[2J\x1b[H");
+    let several = checks.checked.len() > 1;
+    if let Err(e) = report::print(format, &checks.checked, args.suggestions, several) {
+        error!("{:#}", e);
+    }
+    for (checked, error) in checks.checked.iter().zip(&checks.errors) {
+        if let Some(e) = error {
+            error!("{}: {:#}", checked.name, e);
+        }
+    }
+    let n_errors: usize = checks
+        .checked
+        .iter()
+        .map(|c| c.response.matches.len())
+        .sum();
+    info!(
+        "Found {} potential errors in {} file(s), watching for changes (Ctrl-C to quit)",
+        n_errors,
+        checks.checked.len()
+    );
+}
+
+/// Path for the comparison with the paths of the events
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Check the files, then check them again whenever they change, until interrupted. Only the
+/// files found initially are watched.
+pub async fn watch(
+    args: &Flags,
+    backend: &Backend,
+    format: report::OutputFormat,
+    baseline: Option<&Baseline>,
+    filenames: &[PathBuf],
+) -> anyhow::Result<()> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut debouncer =
+        notify_debouncer_mini::new_debouncer(Duration::from_millis(500), move |events| {
+            let _ = tx.send(events);
+        })?;
+    // Watch the directories rather than the files, which editors often replace when saving
+    let index: HashMap<PathBuf, usize> = filenames
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (canonical(f), i))
+        .collect();
+    for dir in index.keys().filter_map(|f| f.parent()).unique() {
+        debouncer
+            .watcher()
+            .watch(dir, RecursiveMode::NonRecursive)?;
+    }
+
+    // Files that could not be checked are reported without matches until they can be
+    let mut checks = Checks {
+        checked: filenames
+            .iter()
+            .map(|f| Checked {
+                path: Some(f.clone()),
+                name: super::inputs::name(Some(f)),
+                text: Default::default(),
+                response: Response {
+                    matches: vec![],
+                    language: Language::default().into(),
+                },
+            })
+            .collect(),
+        errors: filenames.iter().map(|_| None).collect(),
+    };
+    for (i, result) in check(args, backend, baseline, filenames)
+        .await
+        .into_iter()
+        .enumerate()
+    {
+        checks.update(i, result);
+    }
+    redraw(args, format, &checks);
+    while let Some(events) = rx.recv().await {
+        let events = match events {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Watch error: {}", e);
+                continue;
+            }
+        };
+        let changed: Vec<usize> = events
+            .iter()
+            .filter_map(|e| index.get(&canonical(&e.path)).copied())
+            .unique()
+            .sorted()
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+        let paths: Vec<PathBuf> = changed.iter().map(|i| filenames[*i].clone()).collect();
+        debug!("Changed: {:?}", paths);
+        for (i, result) in changed
+            .into_iter()
+            .zip(check(args, backend, baseline, &paths).await)
+        {
+            checks.update(i, result);
+        }
+        redraw(args, format, &checks);
+    }
+    Ok(())
+}
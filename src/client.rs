@@ -1,128 +1,212 @@
-use std::path::PathBuf;
+/// Async client for the API of the server, with timeouts and retries of transient failures.
+use std::time::Duration;
 
-use anyhow::Context;
-use clap::Parser;
-use itertools::Itertools;
 use log::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use ltapiserv_rs::api::{Request, Response};
+use crate::{analysis, api, stats};
 
-/// Run text through a LanguageTool server and display the results.
-#[derive(Parser)]
-struct Flags {
-    /// Filename; if not provided, will read from stdin.
-    filename: Option<PathBuf>,
-    #[clap(long, short, default_value = "en-US")]
-    language: String,
-    /// Server base URL (e.g. http://localhost:8875)
-    #[clap(long, short, env = "LTAPI_SERVER")]
-    server: reqwest::Url,
-    /// JSON output
-    #[clap(long)]
-    json: bool,
-    /// Number of suggestions to display
-    #[clap(long, default_value_t = 3)]
-    suggestions: usize,
-    /// Convert to plaintext with pandoc, removing code blocks. Line numbers are not preserved.
-    #[clap(long, requires = "filename")]
-    pandoc: bool,
+/// Delay before the first retry, doubled for each subsequent one
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid URL: {0}")]
+    Url(String),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("Server returned {status}: {message}")]
+    Status {
+        status: reqwest::StatusCode,
+        message: String,
+    },
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    if let Err(err) = main_impl().await {
-        error!("{}", err);
-        std::process::exit(1);
-    }
-    Ok(())
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    url: reqwest::Url,
+    timeout: Duration,
+    retries: usize,
 }
-async fn main_impl() -> anyhow::Result<()> {
-    let args = Flags::parse();
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .filter_module("nlprule", LevelFilter::Error)
-        .init();
+impl Client {
+    /// Client for the server at a base URL (e.g. http://localhost:8875), with a timeout of 60
+    /// seconds and 2 retries.
+    pub fn new(url: reqwest::Url) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url,
+            timeout: Duration::from_secs(60),
+            retries: 2,
+        }
+    }
+    /// Maximum duration of each attempt, including reading the response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// Number of retries after connection errors, timeouts, or 429, 502, 503 and 504 statuses
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
 
-    let text = if let Some(filename) = &args.filename {
-        if args.pandoc {
-            info!("Converting to plain text with pandoc");
-            let filter = tempfile::NamedTempFile::new()?;
-            std::fs::write(filter.path(), include_str!("filter.lua"))?;
-            let out = std::process::Command::new("pandoc")
-                .arg(filename)
-                .args(["--to", "plain", "--lua-filter"])
-                .arg(filter.path())
-                .stdout(std::process::Stdio::piped())
-                .output()
-                .context("pandoc not found")?;
-            anyhow::ensure!(out.status.success(), "pandoc did not execute successfully");
-            String::from_utf8_lossy(&out.stdout).to_string()
-        } else {
-            std::fs::read_to_string(filename)
-                .with_context(|| format!("Could not open {:?}", filename))?
+    /// Send a request, retrying transient failures
+    async fn send(
+        &self,
+        endpoint: &str,
+        request: impl Fn(reqwest::Url) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let url = self
+            .url
+            .join(endpoint)
+            .map_err(|e| Error::Url(e.to_string()))?;
+        let mut attempt = 0;
+        loop {
+            let result = request(url.clone()).timeout(self.timeout).send().await;
+            let transient = match &result {
+                Ok(response) => matches!(response.status().as_u16(), 429 | 502 | 503 | 504),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if transient && attempt < self.retries {
+                let delay = RETRY_DELAY * 2u32.pow(attempt as u32);
+                attempt += 1;
+                warn!(
+                    "Request to {} failed, retrying in {:?} ({}/{})",
+                    url, delay, attempt, self.retries
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            let response = result?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(Error::Status {
+                    status,
+                    message: response.text().await.unwrap_or_default(),
+                });
+            }
+            return Ok(response);
         }
-    } else {
-        info!("Reading from stdin",);
-        std::io::read_to_string(std::io::stdin())?
-    };
-    debug!("Text to process: {}", text);
+    }
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        Ok(self
+            .send(endpoint, |url| self.http.get(url))
+            .await?
+            .json()
+            .await?)
+    }
+    async fn post<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        form: &(impl Serialize + ?Sized),
+    ) -> Result<T> {
+        Ok(self
+            .send(endpoint, |url| self.http.post(url).form(form))
+            .await?
+            .json()
+            .await?)
+    }
 
-    // Request and read results
-    let endpoint = args.server.join("v2/check")?;
-    info!("Sending request to {}", endpoint);
-    let start = std::time::Instant::now();
-    let client = reqwest::Client::new();
-    let request = Request::new(text.clone(), &args.language);
-    let resp: Response = client
-        .post(endpoint)
-        .form(&request)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
-    info!("Received response in {:?}", start.elapsed());
+    /// Check a text, with the options of the request (language, format, disabled rules and
+    /// categories)
+    pub async fn check(&self, request: &api::Request) -> Result<api::Response> {
+        self.post("v2/check", request).await
+    }
+    /// Readability statistics of a text
+    pub async fn stats(&self, request: &api::Request) -> Result<stats::Stats> {
+        self.post("v2/stats", request).await
+    }
+    /// Tokens, lemmas, part-of-speech and chunk tags of the sentences of a text
+    pub async fn analyze(&self, request: &api::Request) -> Result<Vec<analysis::Sentence>> {
+        self.post("v2/analyze", request).await
+    }
+    pub async fn languages(&self) -> Result<Vec<api::LanguageInfo>> {
+        self.get("v2/languages").await
+    }
+    /// Words of the custom dictionary
+    pub async fn words(&self) -> Result<Vec<String>> {
+        Ok(self.get::<api::WordsResponse>("v2/words").await?.words)
+    }
+    /// Add a word to the custom dictionary, returning whether it was absent
+    pub async fn add_word(&self, word: &str) -> Result<bool> {
+        let request = api::WordRequest { word: word.into() };
+        let response: api::AddWordResponse = self.post("v2/words/add", &request).await?;
+        Ok(response.added)
+    }
+    /// Delete a word from the custom dictionary, returning whether it was present
+    pub async fn delete_word(&self, word: &str) -> Result<bool> {
+        let request = api::WordRequest { word: word.into() };
+        let response: api::DeleteWordResponse = self.post("v2/words/delete", &request).await?;
+        Ok(response.deleted)
+    }
+    /// Whether the server is up, without retries
+    pub async fn health(&self) -> Result<()> {
+        self.clone()
+            .with_retries(0)
+            .send("v2/healthcheck", |url| self.http.get(url))
+            .await?;
+        Ok(())
+    }
+}
 
-    let n_errors = resp.matches.len();
-    if args.json {
-        println!("{}", serde_json::to_string_pretty(&resp)?);
-    } else {
-        // Report errors
-        if resp.matches.is_empty() {
-            info!("No errors found");
-            return Ok(());
-        }
-        let text = std::sync::Arc::new(text);
-        for m in resp.matches {
-            // Get the byte offsets for miette
-            let start = text.char_indices().nth(m.offset).unwrap().0;
-            let end = text.char_indices().nth(m.offset + m.length).unwrap().0;
-            let report = miette::miette!(
-                severity = if m.rule.is_spelling() {
-                    miette::Severity::Warning
-                } else {
-                    miette::Severity::Advice
-                },
-                labels = vec![miette::LabeledSpan::at(
-                    start..end,
-                    m.replacements
-                        .into_iter()
-                        .take(args.suggestions)
-                        .map(|r| r.value)
-                        .join(" / ")
-                ),],
-                // code = m.rule.id,
-                "{}",
-                m.message,
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Server failing with 503 on the first `failures` requests to `/v2/languages`
+    async fn serve(failures: usize) -> reqwest::Url {
+        let count = Arc::new(AtomicUsize::new(0));
+        let app = axum::Router::new()
+            .route(
+                "/v2/languages",
+                axum::routing::get(move || async move {
+                    if count.fetch_add(1, Ordering::SeqCst) < failures {
+                        return Err(axum::http::StatusCode::SERVICE_UNAVAILABLE);
+                    }
+                    Ok(axum::Json(vec![api::LanguageInfo::from(
+                        &api::Language::default(),
+                    )]))
+                }),
             )
-            .with_source_code(text.clone());
-            println!("{:?}", report);
-        }
-    }
-    info!("Found {} potential errors", n_errors);
-    if n_errors > 0 {
-        std::process::exit(1);
+            .route(
+                "/v2/words/add",
+                axum::routing::post(|| async {
+                    (axum::http::StatusCode::BAD_REQUEST, "Invalid word")
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url.parse().unwrap()
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn retries() {
+        let client = Client::new(serve(2).await);
+        let languages = client.languages().await.unwrap();
+        assert_eq!(languages[0].long_code, "en-US");
+        assert_eq!(languages[0].code, "en");
+
+        let client = Client::new(serve(2).await).with_retries(1);
+        assert!(matches!(
+            client.languages().await,
+            Err(Error::Status { status, .. }) if status == 503
+        ));
+        // Errors carry the message of the server
+        assert!(matches!(
+            client.add_word("a b").await,
+            Err(Error::Status { message, .. }) if message == "Invalid word"
+        ));
+    }
 }
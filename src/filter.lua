@@ -1,7 +0,0 @@
-function CodeBlock(el)
-    return ""
-end
-
-function Code(el)
-    return "PLACEHOLDER"
-end
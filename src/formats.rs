@@ -0,0 +1,90 @@
+/// Conversion of markup formats into [`api::Annotations`], so that only the prose gets checked
+/// while the offsets of the matches still refer to the original document.
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api;
+
+pub mod code;
+pub mod commit;
+pub mod html;
+pub mod latex;
+pub mod markdown;
+
+/// Text standing in for code, which should not be checked
+pub const PLACEHOLDER: &str = "PLACEHOLDER";
+
+#[derive(Serialize, Deserialize, clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Plain text
+    #[default]
+    Text,
+    Markdown,
+    Latex,
+    Html,
+    Xml,
+    /// Comments and docstrings of source code
+    Rust,
+    /// C, C++, Java, C#, Kotlin, Swift
+    C,
+    Go,
+    /// JavaScript and TypeScript
+    Javascript,
+    Python,
+    /// Shell, Ruby, Perl
+    Shell,
+    /// Git commit message, without comments and trailers
+    Commit,
+}
+
+impl Format {
+    /// Convert a document into annotations
+    pub fn to_annotations(self, source: &str) -> api::Annotations {
+        match self {
+            Format::Text => api::Annotations {
+                annotation: vec![api::AnnotationElement::Text {
+                    text: source.into(),
+                }],
+            },
+            Format::Markdown => markdown::to_annotations(source),
+            Format::Latex => latex::to_annotations(source),
+            Format::Html | Format::Xml => html::to_annotations(source),
+            Format::Commit => commit::to_annotations(source),
+            _ => code::to_annotations(source, self.syntax().unwrap(), false),
+        }
+    }
+    /// Comment and string syntax, for source code
+    pub fn syntax(self) -> Option<&'static code::Syntax> {
+        match self {
+            Format::Rust => Some(&code::RUST),
+            Format::C => Some(&code::C),
+            Format::Go => Some(&code::GO),
+            Format::Javascript => Some(&code::JAVASCRIPT),
+            Format::Python => Some(&code::PYTHON),
+            Format::Shell => Some(&code::SHELL),
+            _ => None,
+        }
+    }
+    /// Guess the format from the extension of a filename (or the name of git's commit message)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        if path.file_name()? == "COMMIT_EDITMSG" {
+            return Some(Format::Commit);
+        }
+        match path.extension()?.to_str()? {
+            "md" | "markdown" => Some(Format::Markdown),
+            "tex" | "latex" => Some(Format::Latex),
+            "html" | "htm" | "xhtml" => Some(Format::Html),
+            "xml" => Some(Format::Xml),
+            "rs" => Some(Format::Rust),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "cs" | "kt" | "kts"
+            | "swift" => Some(Format::C),
+            "go" => Some(Format::Go),
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" => Some(Format::Javascript),
+            "py" | "pyi" => Some(Format::Python),
+            "sh" | "bash" | "zsh" | "rb" | "pl" => Some(Format::Shell),
+            _ => None,
+        }
+    }
+}
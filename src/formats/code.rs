@@ -0,0 +1,413 @@
+/// Comments, docstrings and (optionally) string literals of source code to annotations: the code
+/// becomes markup, so that only the prose gets checked.
+use super::PLACEHOLDER;
+use crate::api;
+
+/// Paragraph break
+const BREAK: &str = "\n\n";
+
+/// Comment and string syntax of a programming language
+#[derive(Debug)]
+pub struct Syntax {
+    /// Line comment prefixes
+    line_comments: &'static [&'static str],
+    /// Block comment delimiters
+    block_comments: &'static [(&'static str, &'static str)],
+    /// String delimiters, longest first, and whether backslash escapes apply. Triple-quoted
+    /// strings at the start of a line are docstrings, which are always checked.
+    strings: &'static [(&'static str, bool)],
+    /// Whether `'x'` is a character literal (and `'a` a Rust lifetime) rather than a string
+    char_literals: bool,
+    /// Rust raw strings, e.g. `r#"..."#`
+    raw_strings: bool,
+    /// Whether line comments have to follow whitespace, as in shell scripts (`$#`)
+    comment_after_space: bool,
+}
+
+pub const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("\"", true)],
+    char_literals: true,
+    raw_strings: true,
+    comment_after_space: false,
+};
+
+/// C, C++, Java, C#, Kotlin, Swift...
+pub const C: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("\"", true)],
+    char_literals: true,
+    raw_strings: false,
+    comment_after_space: false,
+};
+
+pub const GO: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("\"", true), ("`", false)],
+    char_literals: true,
+    raw_strings: false,
+    comment_after_space: false,
+};
+
+/// JavaScript and TypeScript
+pub const JAVASCRIPT: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("\"", true), ("'", true), ("`", true)],
+    char_literals: false,
+    raw_strings: false,
+    comment_after_space: false,
+};
+
+pub const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    strings: &[("\"\"\"", true), ("'''", true), ("\"", true), ("'", true)],
+    char_literals: false,
+    raw_strings: false,
+    comment_after_space: false,
+};
+
+/// Shell, Ruby, Perl...
+pub const SHELL: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    strings: &[("\"", true), ("'", false)],
+    char_literals: false,
+    raw_strings: false,
+    comment_after_space: true,
+};
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Scanner<'a> {
+    source: &'a str,
+    syntax: &'a Syntax,
+    /// Whether string literals are checked
+    strings: bool,
+    /// Byte position
+    pos: usize,
+    /// Start of the code not converted yet
+    code_start: usize,
+    annotations: api::Annotations,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+    fn previous_char(&self) -> Option<char> {
+        self.source[..self.pos].chars().next_back()
+    }
+
+    /// Convert the code since the last comment or string into markup, separating paragraphs. If
+    /// `continued`, a line comment follows, which continues the previous one if only a line
+    /// break separates them.
+    fn code(&mut self, continued: bool) {
+        let code = &self.source[self.code_start..self.pos];
+        if self.annotations.annotation.is_empty() {
+            self.annotations.push_markup(code, Some(""));
+        } else if code.trim().is_empty() {
+            self.annotations.push_text(code);
+            let lines = code.matches('\n').count();
+            if !(continued && lines == 1 || lines > 1) {
+                self.annotations.push_markup("", Some(BREAK));
+            }
+        } else {
+            self.annotations.push_markup(code, Some(BREAK));
+        }
+        self.code_start = self.pos;
+    }
+
+    /// Prose from a comment, with `inline code` replaced by a placeholder
+    fn prose(&mut self, mut text: &str) {
+        while let Some(start) = text.find('`') {
+            let Some(length) = text[start + 1..].find('`') else {
+                break;
+            };
+            let end = start + length + 2;
+            self.annotations.push_text(&text[..start]);
+            self.annotations
+                .push_markup(&text[start..end], Some(PLACEHOLDER));
+            text = &text[end..];
+        }
+        self.annotations.push_text(text);
+    }
+
+    /// Length of the decoration after a comment delimiter, e.g. the additional `/` of `///`,
+    /// followed by a space.
+    fn decoration(text: &str) -> usize {
+        let trimmed = text.trim_start_matches(['/', '*', '!', '#']);
+        let trimmed = trimmed.strip_prefix(' ').unwrap_or(trimmed);
+        text.len() - trimmed.len()
+    }
+
+    fn line_comment(&mut self, prefix: &str) {
+        self.code(true);
+        let end = self
+            .rest()
+            .find('\n')
+            .map_or(self.source.len(), |i| self.pos + i);
+        let start = self.pos + prefix.len();
+        let start = start + Self::decoration(&self.source[start..end]);
+        self.annotations
+            .push_markup(&self.source[self.pos..start], Some(""));
+        self.prose(&self.source[start..end]);
+        self.pos = end;
+        self.code_start = end;
+    }
+
+    fn block_comment(&mut self, open: &str, close: &str) {
+        self.code(false);
+        let content_start = self.pos + open.len();
+        let end = self.source[content_start..]
+            .find(close)
+            .map_or(self.source.len(), |i| content_start + i);
+        let mut cursor = content_start + Self::decoration(&self.source[content_start..end]);
+        self.annotations
+            .push_markup(&self.source[self.pos..cursor], Some(""));
+        while cursor < end {
+            let line_end = self.source[cursor..end]
+                .find('\n')
+                .map_or(end, |i| cursor + i + 1);
+            self.prose(&self.source[cursor..line_end]);
+            // Indentation and leading `*` of the next line
+            let line = &self.source[line_end..end];
+            let trimmed = line.trim_start_matches([' ', '\t']);
+            let trimmed = trimmed
+                .strip_prefix('*')
+                .map_or(trimmed, |t| t.strip_prefix(' ').unwrap_or(t));
+            cursor = line_end + line.len() - trimmed.len();
+            self.annotations
+                .push_markup(&self.source[line_end..cursor], Some(""));
+        }
+        let comment_end = (end + close.len()).min(self.source.len());
+        self.annotations
+            .push_markup(&self.source[end..comment_end], Some(""));
+        self.pos = comment_end;
+        self.code_start = comment_end;
+    }
+
+    /// Contents of a string literal, with escape sequences and `{}` placeholders as markup
+    fn string_contents(&mut self, content: &str, escapes: bool) {
+        let mut text_start = 0;
+        let mut i = 0;
+        while let Some(c) = content[i..].chars().next() {
+            let rest = &content[i..];
+            let markup: Option<(usize, String)> = if escapes && c == '\\' {
+                let escaped = rest[1..].chars().next();
+                Some((
+                    1 + escaped.map_or(0, char::len_utf8),
+                    match escaped {
+                        Some('n') => "\n".into(),
+                        Some('t') => " ".into(),
+                        Some(c) => c.to_string(),
+                        None => "".into(),
+                    },
+                ))
+            } else if c == '{' || rest.starts_with("${") {
+                rest.find('}').map(|end| (end + 1, PLACEHOLDER.into()))
+            } else {
+                None
+            };
+            match markup {
+                Some((length, interpret_as)) => {
+                    self.annotations.push_text(&content[text_start..i]);
+                    self.annotations
+                        .push_markup(&content[i..i + length], Some(&interpret_as));
+                    i += length;
+                    text_start = i;
+                }
+                None => i += c.len_utf8(),
+            }
+        }
+        self.annotations.push_text(&content[text_start..]);
+    }
+
+    /// String literal, checked if it is a docstring, or if string literals are checked and it
+    /// contains whitespace (which identifiers, keys or MIME types do not).
+    fn string(&mut self, open_length: usize, close: &str, escapes: bool) {
+        let content_start = self.pos + open_length;
+        let mut end = self.source.len();
+        let mut i = content_start;
+        while let Some(c) = self.source[i..].chars().next() {
+            if escapes && c == '\\' {
+                i += 1 + self.source[i + 1..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+            } else if self.source[i..].starts_with(close) {
+                end = i;
+                break;
+            } else {
+                i += c.len_utf8();
+            }
+        }
+        let literal_end = (end + close.len()).min(self.source.len());
+        let content = &self.source[content_start..end];
+        let line_start = self.source[..self.pos].rfind('\n').map_or(0, |i| i + 1);
+        let docstring = close.len() == 3 && self.source[line_start..self.pos].trim().is_empty();
+        if docstring || (self.strings && content.contains(char::is_whitespace)) {
+            self.code(false);
+            self.annotations
+                .push_markup(&self.source[self.pos..content_start], Some(""));
+            if docstring {
+                self.prose(content);
+            } else {
+                self.string_contents(content, escapes);
+            }
+            self.annotations
+                .push_markup(&self.source[end..literal_end], Some(""));
+            self.code_start = literal_end;
+        }
+        self.pos = literal_end;
+    }
+
+    /// Length of a character literal starting at the current position, if any
+    fn char_literal(&self) -> Option<usize> {
+        let rest = &self.rest()[1..];
+        if let Some(escaped) = rest.strip_prefix('\\') {
+            // After the escaped character, e.g. in `'\''` or `'\u{e9}'`
+            let after = 1 + escaped.chars().next()?.len_utf8();
+            rest.get(after..)?
+                .find('\'')
+                .filter(|i| *i < 8)
+                .map(|i| after + i + 2)
+        } else {
+            let c = rest.chars().next()?;
+            rest[c.len_utf8()..]
+                .starts_with('\'')
+                .then_some(c.len_utf8() + 2)
+        }
+    }
+
+    /// Number of `#` of a Rust raw string starting at the current position, if any
+    fn raw_string(&self) -> Option<usize> {
+        let hashes = self.rest().strip_prefix('r')?;
+        let count = hashes.len() - hashes.trim_start_matches('#').len();
+        (hashes[count..].starts_with('"') && !self.previous_char().is_some_and(is_identifier))
+            .then_some(count)
+    }
+
+    fn scan(&mut self) {
+        // Shebang
+        if self.source.starts_with("#!") {
+            self.pos = self.rest().find('\n').unwrap_or(self.source.len());
+        }
+        while let Some(c) = self.rest().chars().next() {
+            let rest = self.rest();
+            if let Some(prefix) = self.syntax.line_comments.iter().find(|p| {
+                rest.starts_with(**p)
+                    && (!self.syntax.comment_after_space
+                        || self.previous_char().is_none_or(char::is_whitespace))
+            }) {
+                self.line_comment(prefix);
+            } else if let Some((open, close)) = self
+                .syntax
+                .block_comments
+                .iter()
+                .find(|(open, _)| rest.starts_with(open))
+            {
+                self.block_comment(open, close);
+            } else if let Some(hashes) = self.raw_string().filter(|_| self.syntax.raw_strings) {
+                let close = format!("\"{}", "#".repeat(hashes));
+                self.string(hashes + 2, &close, false);
+            } else if c == '\'' && self.syntax.char_literals {
+                self.pos += self.char_literal().unwrap_or(1);
+            } else if let Some((quote, escapes)) = self
+                .syntax
+                .strings
+                .iter()
+                .find(|(quote, _)| rest.starts_with(quote))
+            {
+                self.string(quote.len(), quote, *escapes);
+            } else {
+                self.pos += c.len_utf8();
+            }
+        }
+        self.annotations
+            .push_markup(&self.source[self.code_start..], Some(""));
+    }
+}
+
+/// Convert source code into annotations, keeping the comments and docstrings as text, as well as
+/// string literals if `strings` is set.
+pub fn to_annotations(source: &str, syntax: &Syntax, strings: bool) -> api::Annotations {
+    let mut scanner = Scanner {
+        source,
+        syntax,
+        strings,
+        pos: 0,
+        code_start: 0,
+        annotations: Default::default(),
+    };
+    scanner.scan();
+    scanner.annotations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rust() {
+        let source = r##"//! Crate documentation
+//! on two lines.
+
+/// Return `x`, a "value".
+fn f<'a>(x: &'a str) -> char {
+    let _ = r#"raw // string"#; // trailing comment
+    /* A block
+     * comment. */
+    println!("Hello {name}, how are you?\n");
+    '"'
+}
+"##;
+        let annotations = to_annotations(source, &RUST, false);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "Crate documentation\non two lines.\n\nReturn PLACEHOLDER, a \"value\".\n\n\
+             trailing comment\n    \n\nA block\ncomment. "
+        );
+        let annotations = to_annotations(source, &RUST, true);
+        assert!(annotations.text().ends_with(
+            "\n\nraw // string\n\ntrailing comment\n    \n\nA block\ncomment. \n\n\
+             Hello PLACEHOLDER, how are you?\n"
+        ));
+        let text = annotations.text();
+        let start = text.chars().position(|c| c == 'h').unwrap();
+        let (start, end) = annotations.translate_span(start, start + 3);
+        assert_eq!(&source[start..end], "how");
+
+        // Incomplete or non-ASCII escapes
+        for source in ["x = '\\", "x = '\\é' // ok"] {
+            let annotations = to_annotations(source, &RUST, false);
+            assert_eq!(annotations.source(), source);
+        }
+        assert_eq!(to_annotations("x = '\\'' // ok", &RUST, false).text(), "ok");
+    }
+
+    #[test]
+    fn python() {
+        let source = r#"#!/usr/bin/env python
+def f(x):
+    """Compute the value.
+
+    It's a docstring.
+    """
+    return x  # A comment with "quotes"
+"#;
+        let annotations = to_annotations(source, &PYTHON, false);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "Compute the value.\n\n    It's a docstring.\n    \n\nA comment with \"quotes\"\n"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+/// Git commit messages to annotations: comments, trailers and the diff below the scissors line of
+/// `git commit -v` are markup, the subject line is a heading, and issue references and code-like
+/// tokens are replaced by placeholders.
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::PLACEHOLDER;
+use crate::api;
+
+/// Paragraph break
+const BREAK: &str = "\n\n";
+/// Line below which git discards the message
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+/// Trailer tokens without hyphen, besides e.g. `Signed-off-by` or `Co-authored-by`
+const TRAILERS: [&str; 5] = ["Fixes", "Closes", "Resolves", "Refs", "See"];
+
+/// Inline code spans and words
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`\n]+`|\S+").unwrap());
+static ISSUE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([\w.-]+/[\w.-]+)?#\d+$|^[A-Z][A-Z0-9]*-\d+$").unwrap());
+
+/// Git trailer, e.g. `Signed-off-by: Name <email>`
+fn is_trailer(line: &str) -> bool {
+    let Some((token, value)) = line.split_once(": ") else {
+        return false;
+    };
+    !token.is_empty()
+        && !value.trim().is_empty()
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && (token.contains('-') || TRAILERS.contains(&token))
+}
+
+/// Issue reference, e.g. `#12`, `owner/repo#12`, `GH-12` or `PROJ-12`, or URL
+fn is_reference(word: &str) -> bool {
+    ISSUE.is_match(word) || word.starts_with("http://") || word.starts_with("https://")
+}
+
+/// Identifiers, paths, options, expressions and commit hashes
+fn is_code(word: &str) -> bool {
+    let hash = word.len() >= 7
+        && word.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        && word.chars().any(|c| c.is_ascii_digit());
+    let camel_case = word
+        .chars()
+        .zip(word.chars().skip(1))
+        .any(|(a, b)| a.is_lowercase() && b.is_uppercase());
+    // E.g. `main.rs` or `self.value`, but not `e.g`
+    let dotted = word.rsplit_once('.').is_some_and(|(a, b)| {
+        !a.is_empty() && b.len() > 1 && b.chars().all(|c| c.is_alphanumeric())
+    });
+    hash || camel_case
+        || dotted
+        || (word.starts_with("--") && word.len() > 2)
+        || ["_", "::", "()", "/", "=", "->", "<", ">", "{", "["]
+            .iter()
+            .any(|s| word.contains(s))
+}
+
+/// Span of a word to replace by a placeholder, without the surrounding punctuation
+fn code_span(word: &str) -> Option<(usize, usize)> {
+    let start = word.len() - word.trim_start_matches(['(', '"', '\'']).len();
+    let mut end = word
+        .trim_end_matches(['.', ',', ';', ':', '!', '?', '"', '\''])
+        .len();
+    if word[..end].ends_with(')') && !word[start..end].contains('(') {
+        end -= 1;
+    }
+    let core = &word[start..end.max(start)];
+    (!core.is_empty() && (is_reference(core) || is_code(core))).then_some((start, end))
+}
+
+fn push_line(annotations: &mut api::Annotations, line: &str) {
+    let mut cursor = 0;
+    for m in TOKEN.find_iter(line) {
+        let (start, end) = if m.as_str().starts_with('`') {
+            (m.start(), m.end())
+        } else if let Some((start, end)) = code_span(m.as_str()) {
+            (m.start() + start, m.start() + end)
+        } else {
+            continue;
+        };
+        annotations.push_text(&line[cursor..start]);
+        annotations.push_markup(&line[start..end], Some(PLACEHOLDER));
+        cursor = end;
+    }
+    annotations.push_text(&line[cursor..]);
+}
+
+pub fn to_annotations(source: &str) -> api::Annotations {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let end = lines
+        .iter()
+        .position(|l| l.trim_end() == SCISSORS)
+        .unwrap_or(lines.len());
+    let is_comment = |i: usize| i >= end || lines[i].starts_with('#');
+    let is_blank = |i: usize| lines[i].trim().is_empty();
+    let content: Vec<usize> = (0..end).filter(|i| !is_comment(*i)).collect();
+    let subject = content.iter().copied().find(|i| !is_blank(*i));
+    // The trailers are the last paragraph, if it is not the subject
+    let mut last_paragraph: Vec<usize> = content
+        .iter()
+        .rev()
+        .copied()
+        .skip_while(|i| is_blank(*i))
+        .take_while(|i| !is_blank(*i))
+        .collect();
+    if last_paragraph.contains(&subject.unwrap_or(0))
+        || !last_paragraph.iter().all(|i| is_trailer(lines[*i]))
+    {
+        last_paragraph.clear();
+    }
+
+    let mut annotations = api::Annotations::default();
+    for (i, line) in lines.iter().enumerate() {
+        if is_comment(i) || last_paragraph.contains(&i) {
+            annotations.push_markup(line, None);
+            continue;
+        }
+        push_line(&mut annotations, line);
+        // A heading, even if the body follows directly
+        if Some(i) == subject
+            && content
+                .iter()
+                .find(|j| **j > i)
+                .is_some_and(|j| !is_blank(*j))
+        {
+            annotations.push_markup("", Some(BREAK));
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn commit_message() {
+        let source = "Fix the parsing of `foo_bar` in main.rs\n\
+                      Calls parseValue() instead, see #12 and GH-34 (since 1a2b3c4d).\n\
+                      \n\
+                      Signed-off-by: A Person <a@example.com>\n\
+                      Co-authored-by: Someone <s@example.com>\n\
+                      # Please enter the commit message for your changes.\n\
+                      # ------------------------ >8 ------------------------\n\
+                      diff --git a/main.rs b/main.rs\n";
+        let annotations = super::to_annotations(source);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "Fix the parsing of PLACEHOLDER in PLACEHOLDER\n\n\n\
+             Calls PLACEHOLDER instead, see PLACEHOLDER and PLACEHOLDER (since PLACEHOLDER).\n\n"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+/// HTML and XML to annotations: tags become markup, block elements paragraph breaks, and
+/// entities are decoded while keeping the offsets in the original document.
+use super::PLACEHOLDER;
+use crate::api;
+
+/// Paragraph break
+const BREAK: &str = "\n\n";
+
+/// Elements that delimit paragraphs. This includes some common XML (e.g. DocBook) elements.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "section",
+    "summary",
+    "table",
+    "td",
+    "th",
+    "title",
+    "tr",
+    "ul",
+    // XML
+    "chapter",
+    "entry",
+    "item",
+    "listitem",
+    "para",
+    "row",
+];
+
+/// Elements whose contents are skipped, with the text they are interpreted as.
+const SKIPPED_ELEMENTS: &[(&str, &str)] = &[
+    ("code", PLACEHOLDER),
+    ("kbd", PLACEHOLDER),
+    ("samp", PLACEHOLDER),
+    ("pre", BREAK),
+    ("script", ""),
+    ("style", ""),
+    ("svg", ""),
+    ("math", PLACEHOLDER),
+    ("textarea", ""),
+];
+
+/// Decode a character reference, e.g. `amp` or `#x27`
+fn decode_entity(entity: &str) -> Option<String> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = if let Some(hex) = number.strip_prefix(['x', 'X']) {
+            u32::from_str_radix(hex, 16).ok()?
+        } else {
+            number.parse().ok()?
+        };
+        return char::from_u32(code).map(|c| c.to_string());
+    }
+    Some(
+        match entity {
+            "amp" => "&",
+            "lt" => "<",
+            "gt" => ">",
+            "quot" => "\"",
+            "apos" => "'",
+            "nbsp" | "ensp" | "emsp" | "thinsp" => " ",
+            "shy" | "zwj" | "zwnj" => "",
+            "ndash" => "–",
+            "mdash" => "—",
+            "hellip" => "…",
+            "lsquo" => "‘",
+            "rsquo" => "’",
+            "ldquo" => "“",
+            "rdquo" => "”",
+            "laquo" => "«",
+            "raquo" => "»",
+            "copy" => "©",
+            "reg" => "®",
+            "trade" => "™",
+            "deg" => "°",
+            "euro" => "€",
+            "times" => "×",
+            "eacute" => "é",
+            "egrave" => "è",
+            "agrave" => "à",
+            "ccedil" => "ç",
+            "auml" => "ä",
+            "ouml" => "ö",
+            "uuml" => "ü",
+            "szlig" => "ß",
+            _ => return None,
+        }
+        .into(),
+    )
+}
+
+/// Name of the element of a tag (starting after `<`), lowercased, and whether it is a closing tag
+fn tag_name(tag: &str) -> (String, bool) {
+    let (tag, closing) = tag.strip_prefix('/').map_or((tag, false), |t| (t, true));
+    (
+        tag.chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == ':')
+            .collect::<String>()
+            .to_lowercase(),
+        closing,
+    )
+}
+
+/// Position of the closing tag `</name` in `source`, ignoring ASCII case. The offsets stay those
+/// of `source`, contrary to searching a lowercased copy.
+fn find_closing_tag(source: &str, name: &str) -> Option<usize> {
+    let closing_tag = format!("</{}", name);
+    source.match_indices("</").map(|(i, _)| i).find(|i| {
+        source
+            .as_bytes()
+            .get(*i..*i + closing_tag.len())
+            .is_some_and(|b| b.eq_ignore_ascii_case(closing_tag.as_bytes()))
+    })
+}
+
+/// End of the tag starting at `start` (with `<`), skipping quoted attribute values
+fn tag_end(source: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (i, c) in source[start..].char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) => return start + i + 1,
+            _ => {}
+        }
+    }
+    source.len()
+}
+
+/// Convert an HTML or XML document into annotations
+pub fn to_annotations(source: &str) -> api::Annotations {
+    let mut annotations = api::Annotations::default();
+    let mut pos = 0;
+    let mut text_start = 0;
+    while let Some(offset) = source[pos..].find(['<', '&']) {
+        pos += offset;
+        let rest = &source[pos..];
+        // Markup starting at `pos`, with its end and interpretation
+        let markup: Option<(usize, String)> = if let Some(entity) = rest.strip_prefix('&') {
+            entity.find(';').filter(|end| *end <= 32).and_then(|end| {
+                decode_entity(&entity[..end]).map(|decoded| (pos + end + 2, decoded))
+            })
+        } else if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(source.len(), |i| pos + i + 3);
+            Some((end, "".into()))
+        } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            // The contents of CDATA sections are text
+            annotations.push_text(&source[text_start..pos]);
+            annotations.push_markup("<![CDATA[", None);
+            let end = cdata.find("]]>").map_or(source.len(), |i| pos + 9 + i);
+            annotations.push_text(&source[pos + 9..end]);
+            pos = (end + 3).min(source.len());
+            annotations.push_markup(&source[end..pos], None);
+            text_start = pos;
+            continue;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            Some((tag_end(source, pos), "".into()))
+        } else if rest[1..].starts_with(|c: char| c.is_alphabetic() || c == '/') {
+            let end = tag_end(source, pos);
+            let (name, closing) = tag_name(&rest[1..]);
+            let self_closing = source[..end].ends_with("/>");
+            if let Some((_, interpret_as)) = SKIPPED_ELEMENTS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .filter(|_| !closing && !self_closing)
+            {
+                let end = find_closing_tag(&source[end..], &name)
+                    .map_or(source.len(), |i| tag_end(source, end + i));
+                Some((end, interpret_as.to_string()))
+            } else if name == "br" {
+                Some((end, "\n".into()))
+            } else if BLOCK_ELEMENTS.contains(&name.as_str()) {
+                Some((end, BREAK.into()))
+            } else {
+                Some((end, "".into()))
+            }
+        } else {
+            None
+        };
+        match markup {
+            Some((end, mut interpret_as)) => {
+                annotations.push_text(&source[text_start..pos]);
+                // Avoid accumulating breaks between consecutive block elements
+                if interpret_as == BREAK
+                    && annotations
+                        .annotation
+                        .last()
+                        .is_some_and(|a| a.text().ends_with(BREAK))
+                {
+                    interpret_as.clear();
+                }
+                annotations.push_markup(&source[pos..end], Some(&interpret_as));
+                pos = end;
+                text_start = end;
+            }
+            // Not markup, e.g. a lone `&` or `<`
+            None => pos += 1,
+        }
+    }
+    annotations.push_text(&source[text_start..]);
+    annotations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn html() {
+        let source = r#"<!DOCTYPE html>
+<html><head><style>p { color: red; }</style></head>
+<body><h1 class="a>b">Caf&eacute; &amp; bar</h1><p>Some <b>bold</b> text,
+<code>x &lt; y</code> and<br>a &#x27;quote&#39;.<!-- comment --></p>
+<pre>let x = 1;</pre></body></html>"#;
+        let annotations = to_annotations(source);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "\n\n\n\n\n\nCafé & bar\n\nSome bold text,\nPLACEHOLDER and\na 'quote'.\n\n\n\n\n"
+        );
+        let text = annotations.text();
+        let start = text.chars().position(|c| c == 'C').unwrap();
+        let (start, end) = annotations.translate_span(start, start + 4);
+        assert_eq!(&source[start..end], "Caf&eacute;");
+
+        // Characters whose lowercase is longer, in a skipped element
+        let source = format!("<code>{}</CODE> text", "İ".repeat(20));
+        let annotations = to_annotations(&source);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(annotations.text(), "PLACEHOLDER text");
+    }
+}
@@ -0,0 +1,336 @@
+/// LaTeX to annotations, with a lightweight scanner keeping the text of sections and paragraphs,
+/// and turning commands, math and verbatim environments into markup.
+use super::PLACEHOLDER;
+use crate::api;
+
+/// Paragraph break
+const BREAK: &str = "\n\n";
+
+/// Commands whose (last) argument is text to be checked.
+const TEXT_COMMANDS: &[&str] = &[
+    "emph",
+    "textbf",
+    "textit",
+    "textsc",
+    "textsf",
+    "texttt",
+    "textrm",
+    "underline",
+    "footnote",
+    "caption",
+    "title",
+    "author",
+    "mbox",
+    "hbox",
+    "text",
+];
+
+/// Sectioning commands, whose argument is checked as a heading.
+const SECTION_COMMANDS: &[&str] = &[
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+/// Commands standing for a word, e.g. a reference or a citation.
+const WORD_COMMANDS: &[&str] = &[
+    "ref", "eqref", "cref", "Cref", "autoref", "pageref", "cite", "citep", "citet", "url", "href",
+];
+
+/// Environments whose contents are not text.
+const SKIPPED_ENVIRONMENTS: &[&str] = &[
+    "verbatim",
+    "Verbatim",
+    "lstlisting",
+    "minted",
+    "comment",
+    "equation",
+    "equation*",
+    "align",
+    "align*",
+    "gather",
+    "gather*",
+    "multline",
+    "multline*",
+    "displaymath",
+    "math",
+    "tikzpicture",
+    "tabular",
+];
+
+struct Scanner<'a> {
+    source: &'a str,
+    /// Byte position
+    pos: usize,
+    annotations: api::Annotations,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+    fn markup_until(&mut self, end: usize, interpret_as: &str) {
+        self.annotations
+            .push_markup(&self.source[self.pos..end], Some(interpret_as));
+        self.pos = end;
+    }
+    /// End of a balanced group starting at `start` with `open`, or of the source
+    fn group_end(&self, start: usize, open: char, close: char) -> usize {
+        self.group_close(start, open, close)
+            .unwrap_or(self.source.len())
+    }
+    /// End of a balanced group starting at `start` with `open`, if it is closed
+    fn group_close(&self, start: usize, open: char, close: char) -> Option<usize> {
+        let mut depth = 0;
+        let mut escaped = false;
+        for (i, c) in self.source[start..].char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(start + i + c.len_utf8());
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+    /// Skip whitespace (without paragraph breaks) and optional arguments after a command,
+    /// returning the position of the next mandatory argument, if any.
+    fn arguments_start(&self, mut pos: usize) -> (usize, Option<usize>) {
+        loop {
+            let rest = &self.source[pos..];
+            let trimmed = rest.trim_start_matches([' ', '\t']);
+            let trimmed = trimmed
+                .strip_prefix('\n')
+                .map_or(trimmed, |t| t.trim_start_matches([' ', '\t']));
+            let next = pos + rest.len() - trimmed.len();
+            if trimmed.starts_with('[') {
+                pos = self.group_end(next, '[', ']');
+            } else if trimmed.starts_with('*') {
+                pos = next + 1;
+            } else if trimmed.starts_with('{') {
+                return (pos, Some(next));
+            } else {
+                return (pos, None);
+            }
+        }
+    }
+    /// End of all the arguments of a command, mandatory or optional
+    fn arguments_end(&self, pos: usize) -> usize {
+        let (mut pos, mut next) = self.arguments_start(pos);
+        while let Some(start) = next {
+            (pos, next) = self.arguments_start(self.group_end(start, '{', '}'));
+        }
+        pos
+    }
+
+    /// Convert a command, returning `Some(section)` if it opens a group of text to be converted
+    /// (a heading if `section` is set).
+    fn command(&mut self) -> Option<bool> {
+        let start = self.pos;
+        let name_len = self.rest()[1..]
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(self.rest().len() - 1);
+        if name_len == 0 {
+            // Control symbol, e.g. \% or \\
+            let symbol = self.rest()[1..].chars().next();
+            let end = start + 1 + symbol.map_or(0, char::len_utf8);
+            let interpret_as = match symbol {
+                Some('\\') => "\n".to_string(),
+                Some(c @ ('%' | '&' | '$' | '#' | '_' | '{' | '}')) => c.to_string(),
+                Some(' ') => " ".to_string(),
+                _ => "".to_string(),
+            };
+            self.markup_until(end, &interpret_as);
+            return None;
+        }
+        let name = &self.rest()[1..1 + name_len];
+        let after_name = start + 1 + name_len;
+        match name {
+            "begin" => {
+                let (_, arg) = self.arguments_start(after_name);
+                // An unclosed environment name (e.g. while typing) is markup until the end
+                let env = arg.and_then(|a| {
+                    let end = self.group_close(a, '{', '}')?;
+                    Some(self.source[a + 1..end - 1].trim())
+                });
+                if let Some(env) = env.filter(|e| SKIPPED_ENVIRONMENTS.contains(e)) {
+                    let end_tag = format!("\\end{{{}}}", env);
+                    let end = self.source[after_name..]
+                        .find(&end_tag)
+                        .map_or(self.source.len(), |i| after_name + i + end_tag.len());
+                    self.markup_until(end, BREAK);
+                } else {
+                    let end = self.arguments_end(after_name);
+                    self.markup_until(end, BREAK);
+                }
+            }
+            "end" | "item" | "par" | "maketitle" | "tableofcontents" => {
+                let end = match name {
+                    "end" | "item" => self.arguments_end(after_name),
+                    _ => after_name,
+                };
+                self.markup_until(end, BREAK);
+            }
+            _ if SECTION_COMMANDS.contains(&name) || TEXT_COMMANDS.contains(&name) => {
+                let section = SECTION_COMMANDS.contains(&name);
+                let (_, arg) = self.arguments_start(after_name);
+                let Some(arg) = arg else {
+                    self.markup_until(after_name, "");
+                    return None;
+                };
+                self.markup_until(arg + 1, if section { BREAK } else { "" });
+                return Some(section);
+            }
+            _ if WORD_COMMANDS.contains(&name) => {
+                let end = self.arguments_end(after_name);
+                self.markup_until(end, PLACEHOLDER);
+            }
+            _ => {
+                // Other commands are removed along with their arguments.
+                let end = self.arguments_end(after_name);
+                self.markup_until(end, "");
+            }
+        }
+        None
+    }
+
+    fn math(&mut self) {
+        let rest = self.rest();
+        let (close, display) = if rest.starts_with("$$") {
+            ("$$", true)
+        } else if rest.starts_with("\\[") {
+            ("\\]", true)
+        } else if rest.starts_with("\\(") {
+            ("\\)", false)
+        } else {
+            ("$", false)
+        };
+        let open_len = close.len();
+        let end = self.source[self.pos + open_len..]
+            .find(close)
+            .map_or(self.source.len(), |i| self.pos + open_len + i + close.len());
+        self.markup_until(end, if display { BREAK } else { PLACEHOLDER });
+    }
+
+    /// Convert the source until its end
+    fn scan(&mut self) {
+        // Open groups, with whether they are headings (followed by a paragraph break)
+        let mut groups: Vec<bool> = vec![];
+        let mut text_start = self.pos;
+        while let Some(c) = self.rest().chars().next() {
+            let special = matches!(c, '\\' | '%' | '$' | '{' | '}' | '~');
+            if !special {
+                self.pos += c.len_utf8();
+                continue;
+            }
+            self.annotations
+                .push_text(&self.source[text_start..self.pos]);
+            match c {
+                '%' => {
+                    // Comment, including the line break
+                    let end = self
+                        .rest()
+                        .find('\n')
+                        .map_or(self.source.len(), |i| self.pos + i + 1);
+                    self.markup_until(end, "");
+                }
+                '$' => self.math(),
+                '\\' if self.rest().starts_with("\\[") || self.rest().starts_with("\\(") => {
+                    self.math()
+                }
+                '\\' => groups.extend(self.command()),
+                '~' => self.markup_until(self.pos + 1, " "),
+                '{' => {
+                    self.markup_until(self.pos + 1, "");
+                    groups.push(false);
+                }
+                '}' => {
+                    self.markup_until(self.pos + 1, "");
+                    if groups.pop() == Some(true) {
+                        self.annotations.push_markup("", Some(BREAK));
+                    }
+                }
+                _ => unreachable!(),
+            }
+            text_start = self.pos;
+        }
+        self.annotations.push_text(&self.source[text_start..]);
+        // Unclosed headings
+        for section in groups.into_iter().rev() {
+            if section {
+                self.annotations.push_markup("", Some(BREAK));
+            }
+        }
+    }
+}
+
+/// Convert a LaTeX document into annotations. Only the body is converted if the document
+/// contains a `\begin{document}`.
+pub fn to_annotations(source: &str) -> api::Annotations {
+    let mut scanner = Scanner {
+        source,
+        pos: 0,
+        annotations: Default::default(),
+    };
+    if let Some(begin) = source.find("\\begin{document}") {
+        scanner.markup_until(begin + "\\begin{document}".len(), "");
+    }
+    scanner.scan();
+    scanner.annotations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latex() {
+        let source = r"\documentclass{article}
+\usepackage[utf8]{inputenc}
+\begin{document}
+\section{Introduction}
+Some \emph{emphasized} text~\cite{knuth}, with $x^2$ and 50\% of
+% a comment
+\textbf{bold}.
+\begin{lstlisting}
+let x = 1;
+\end{lstlisting}
+\end{document}
+";
+        let annotations = to_annotations(source);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "\n\n\nIntroduction\n\n\nSome emphasized text PLACEHOLDER, with PLACEHOLDER and 50% of\n\
+             bold.\n\n\n\n\n\n\n"
+        );
+        let text = annotations.text();
+        let start = text.find("emphasized").unwrap();
+        let (start, end) = annotations.translate_span(start, start + "emphasized".len());
+        assert_eq!(&source[start..end], "emphasized");
+
+        // Unclosed environment names
+        for source in [r"text \begin{", r"\begin{é"] {
+            let annotations = to_annotations(source);
+            assert_eq!(annotations.source(), source);
+        }
+
+        // Deeply nested groups
+        for open in ["{", "\\emph{"] {
+            let source = format!("{}text{}", open.repeat(100_000), "}".repeat(100_000));
+            let annotations = to_annotations(&source);
+            assert_eq!(annotations.source(), source);
+            assert_eq!(annotations.text(), "text");
+        }
+    }
+}
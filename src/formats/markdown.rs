@@ -0,0 +1,163 @@
+/// Markdown (CommonMark with GitHub extensions) to annotations, based on [`pulldown_cmark`].
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use super::PLACEHOLDER;
+use crate::api;
+
+/// Paragraph break
+const BREAK: &str = "\n\n";
+
+struct Converter<'a> {
+    source: &'a str,
+    annotations: api::Annotations,
+    /// Byte offset up to which the source has been converted
+    cursor: usize,
+    /// Whether a block boundary was crossed since the last text
+    pending_break: bool,
+    /// Whether text or markup was converted, before which no break is needed
+    started: bool,
+}
+
+impl Converter<'_> {
+    /// Convert the source up to `end` as markup, adding a paragraph break if needed.
+    fn gap(&mut self, end: usize) {
+        let gap = &self.source[self.cursor..end];
+        self.annotations.push_markup(gap, None);
+        // A gap with block markers (e.g. `\n\n# `) is markup without text, blank lines included
+        let has_break = self
+            .annotations
+            .annotation
+            .last()
+            .is_some_and(|a| a.text().ends_with(BREAK));
+        if self.pending_break && self.started && !has_break {
+            self.annotations.push_markup("", Some(BREAK));
+        }
+        self.pending_break = false;
+        self.started = true;
+        self.cursor = end;
+    }
+    /// Convert the source span as markup, interpreted as the given text.
+    fn markup(&mut self, range: std::ops::Range<usize>, interpret_as: &str) {
+        if range.start < self.cursor {
+            return;
+        }
+        self.gap(range.start);
+        self.annotations
+            .push_markup(&self.source[range.clone()], Some(interpret_as));
+        self.cursor = range.end;
+    }
+    fn text(&mut self, range: std::ops::Range<usize>, text: &str) {
+        if range.start < self.cursor {
+            return;
+        }
+        self.gap(range.start);
+        let source = &self.source[range.clone()];
+        if source == text {
+            self.annotations.push_text(text);
+        } else {
+            // E.g. escaped characters and entities
+            self.annotations.push_markup(source, Some(text));
+        }
+        self.cursor = range.end;
+    }
+}
+
+fn is_block(tag: &TagEnd) -> bool {
+    !matches!(
+        tag,
+        TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Superscript
+            | TagEnd::Subscript
+            | TagEnd::Link
+            | TagEnd::Image
+    )
+}
+
+/// Convert a Markdown document into annotations
+pub fn to_annotations(source: &str) -> api::Annotations {
+    let mut converter = Converter {
+        source,
+        annotations: Default::default(),
+        cursor: 0,
+        pending_break: false,
+        started: false,
+    };
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_MATH;
+    for (event, range) in Parser::new_ext(source, options).into_offset_iter() {
+        match event {
+            Event::Text(text) => converter.text(range, &text),
+            Event::SoftBreak | Event::HardBreak => converter.markup(range, "\n"),
+            Event::Code(_) | Event::InlineMath(_) => converter.markup(range, PLACEHOLDER),
+            Event::Start(
+                Tag::CodeBlock(_)
+                | Tag::HtmlBlock
+                | Tag::MetadataBlock(_)
+                | Tag::Image { .. }
+                | Tag::Link {
+                    link_type: LinkType::Autolink | LinkType::Email,
+                    ..
+                },
+            ) => {
+                // The contents are skipped, as they start before the cursor.
+                let block = !matches!(&event, Event::Start(Tag::Image { .. } | Tag::Link { .. }));
+                converter.markup(range, if block { BREAK } else { PLACEHOLDER });
+            }
+            Event::Start(tag) if is_block(&tag.to_end()) => converter.pending_break = true,
+            Event::End(tag) if is_block(&tag) => converter.pending_break = true,
+            Event::DisplayMath(_) | Event::Rule => converter.markup(range, BREAK),
+            Event::Html(_)
+            | Event::InlineHtml(_)
+            | Event::FootnoteReference(_)
+            | Event::TaskListMarker(_) => converter.markup(range, ""),
+            Event::Start(_) | Event::End(_) => {}
+        }
+    }
+    converter.pending_break = false;
+    converter.gap(source.len());
+    converter.annotations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn markdown() {
+        let source = "# Title\nSome *emphasis*, `code` and [a link](https://example.com).\n\n\
+                      ```rust\nlet x = 1;\n```\n\n- Item &amp; <b>item</b>\n- Item two\n";
+        let annotations = to_annotations(source);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "Title\n\n\nSome emphasis, PLACEHOLDER and a link.\n\n\n\n\
+             Item & item\n\nItem two\n"
+        );
+        // Span of "link" in the text, mapped to the source
+        let text = annotations.text();
+        let start = text.find("link").unwrap();
+        let (start, end) = annotations.translate_span(start, start + 4);
+        assert_eq!(&source[start..end], "link");
+        // Span of the placeholder
+        let start = text.find(PLACEHOLDER).unwrap();
+        let (start, end) = annotations.translate_span(start, start + PLACEHOLDER.len());
+        assert_eq!(&source[start..end], "`code`");
+
+        // Blocks whose markers follow a blank line
+        let source = "Para one\n\n# Heading\n\nText\n\n- item one\n\n> Quote\n\n\
+                      | a | b |\n|---|---|\n| c | d |\n\nIntro\n\n1. First\n";
+        let annotations = to_annotations(source);
+        assert_eq!(annotations.source(), source);
+        assert_eq!(
+            annotations.text(),
+            "Para one\n\nHeading\n\nText\n\nitem one\n\nQuote\n\n\
+             a\n\nb\n\nc\n\nd\n\nIntro\n\nFirst\n"
+        );
+    }
+}
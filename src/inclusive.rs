@@ -0,0 +1,186 @@
+/// Inclusive language checker, flagging terms from a replaceable list and suggesting alternatives.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use log::*;
+
+use crate::api;
+
+/// Default terms, in the format documented in [`Terms::parse`].
+const DEFAULT_TERMS: &str = include_str!("inclusive.txt");
+
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct Config {
+    /// Disable the inclusive language checks
+    #[clap(long = "no-inclusive")]
+    pub disable: bool,
+    /// File with terms extending or overriding the default list, one `term = alternative, ...`
+    /// per line. A term without alternatives is removed from the list.
+    #[clap(long = "inclusive-terms")]
+    pub terms: Option<PathBuf>,
+}
+
+pub struct Terms {
+    /// Lowercase term to alternatives
+    terms: HashMap<String, Vec<String>>,
+    regex: Option<regex::Regex>,
+}
+impl Default for Terms {
+    fn default() -> Self {
+        Self::new(&Default::default()).unwrap()
+    }
+}
+impl Terms {
+    /// Parse a list of terms, with lines of the form `term = alternative, alternative, ...`.
+    /// Empty lines and lines starting with `#` are ignored.
+    fn parse(contents: &str) -> anyhow::Result<Vec<(String, Vec<String>)>> {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| {
+                let (term, alternatives) = l
+                    .split_once('=')
+                    .with_context(|| format!("Expected `term = alternatives`, got {:?}", l))?;
+                Ok((
+                    term.trim().to_lowercase(),
+                    alternatives
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(String::from)
+                        .collect(),
+                ))
+            })
+            .collect()
+    }
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let mut terms = HashMap::new();
+        if config.disable {
+            return Ok(Self { terms, regex: None });
+        }
+        terms.extend(Self::parse(DEFAULT_TERMS)?);
+        if let Some(filename) = &config.terms {
+            let contents = std::fs::read_to_string(filename)
+                .with_context(|| format!("Failed to read inclusive terms from {:?}", filename))?;
+            terms.extend(Self::parse(&contents)?);
+        }
+        terms.retain(|_, alternatives| !alternatives.is_empty());
+        info!("Loaded {} inclusive language terms", terms.len());
+
+        // Longest terms first, so that e.g. "blacklisted" is preferred over "blacklist".
+        let mut sorted: Vec<_> = terms.keys().collect();
+        sorted.sort_by_key(|t| std::cmp::Reverse(t.len()));
+        let regex = (!sorted.is_empty())
+            .then(|| {
+                regex::Regex::new(&format!(
+                    r"(?i)\b({})\b",
+                    sorted
+                        .into_iter()
+                        .map(|t| regex::escape(t))
+                        .collect::<Vec<_>>()
+                        .join("|")
+                ))
+            })
+            .transpose()?;
+        Ok(Self { terms, regex })
+    }
+
+    /// Check the text contained in the annotations
+    pub fn check(&self, annotations: &api::Annotations) -> Vec<api::Match> {
+        let Some(regex) = &self.regex else {
+            return vec![];
+        };
+        let text = annotations.text();
+        // Byte to character offsets, incrementally as the matches are ordered.
+        let (mut byte, mut char) = (0, 0);
+        let mut to_char = |offset: usize| {
+            char += text[byte..offset].chars().count();
+            byte = offset;
+            char
+        };
+        regex
+            .find_iter(&text)
+            .map(|term| {
+                let alternatives = &self.terms[&term.as_str().to_lowercase()];
+                let mut message = format!(
+                    "Consider using more inclusive language than '{}'.",
+                    term.as_str()
+                );
+                write!(message, " Alternatives: {}.", alternatives.join(", ")).unwrap();
+                let (start, end) = (to_char(term.start()), to_char(term.end()));
+                let (start, end) = annotations.translate_span(start, end);
+                api::Match {
+                    message,
+                    offset: start,
+                    length: end - start,
+                    replacements: alternatives
+                        .iter()
+                        .map(|a| restore_case(term.as_str(), a).into())
+                        .collect(),
+                    rule: api::Rule::inclusive(),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Apply the case of `source` (lowercase, capitalized, or uppercase) to `target`.
+fn restore_case(source: &str, target: &str) -> String {
+    if source.chars().count() > 1 && source.chars().all(|c| !c.is_lowercase()) {
+        target.to_uppercase()
+    } else if source.chars().next().is_some_and(char::is_uppercase) {
+        let mut chars = target.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        target.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn terms() -> anyhow::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        std::fs::write(file.path(), "# Comment\nguys =\nfoobar = baz, qux\n")?;
+        let terms = Terms::new(&Config {
+            disable: false,
+            terms: Some(file.path().into()),
+        })?;
+        let annotations = api::Request::new(
+            "Whitelists and blacklisted FOOBAR, hi guys, whitelisting. Chairmen, foobars.".into(),
+            "en-US",
+        )
+        .annotations()?;
+        let matches = terms.check(&annotations);
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (
+                    m.offset,
+                    m.length,
+                    m.replacements
+                        .iter()
+                        .map(|r| r.value.as_str())
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 10, vec!["Allowlists"]),
+                (15, 11, vec!["denylisted", "blocked"]),
+                (27, 6, vec!["BAZ", "QUX"]),
+                (44, 12, vec!["allowlisting", "allowing"]),
+                (58, 8, vec!["Chairs", "Chairpersons"]),
+            ]
+        );
+        Ok(())
+    }
+}
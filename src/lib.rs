@@ -1,2 +1,10 @@
+pub mod analysis;
 pub mod api;
 pub mod checkers;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod formats;
+pub mod inclusive;
+pub mod repetition;
+pub mod stats;
+pub mod typography;
@@ -0,0 +1,243 @@
+/// Repetition checker, detecting doubled words ("the the") and content words repeated nearby.
+///
+/// Contrary to [`nlprule`] rules, this operates across sentences, so that repetitions split by a
+/// line break or by markup (which gets removed from the text) are caught as well.
+use std::collections::VecDeque;
+
+use crate::api;
+
+/// Words that can legitimately be doubled, e.g. "had had".
+const ALLOWED_DOUBLES: &[&str] = &["had", "that", "bye", "ha", "knock", "no", "so", "very"];
+
+/// Punctuation tokens that do not interrupt a repetition, e.g. in `the "the"`.
+const TRANSPARENT: &[&str] = &[
+    "\"", "'", "“", "”", "‘", "’", "(", ")", "[", "]", "*", "_", "`",
+];
+
+/// Part-of-speech tag prefixes of content words (nouns, verbs, adjectives, adverbs).
+const CONTENT_POS: &[&str] = &["NN", "VB", "JJ", "RB"];
+
+/// Lemmas of auxiliary verbs, which are not considered as content words.
+const AUXILIARIES: &[&str] = &["be", "have", "do"];
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Config {
+    /// Report content words repeated within this number of words (0 to disable).
+    #[clap(long = "repetition-window", default_value_t = 0)]
+    pub window: usize,
+    /// Minimal length of the content words considered for nearby repetitions.
+    #[clap(long = "repetition-min-length", default_value_t = 4)]
+    pub min_length: usize,
+    /// Additional words that can legitimately be doubled (comma-separated).
+    #[clap(long = "repetition-allow", value_delimiter = ',')]
+    pub allow: Vec<String>,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window: 0,
+            min_length: 4,
+            allow: vec![],
+        }
+    }
+}
+impl Config {
+    fn is_allowed_double(&self, word: &str) -> bool {
+        ALLOWED_DOUBLES.contains(&word) || self.allow.iter().any(|w| w.to_lowercase() == word)
+    }
+}
+
+#[derive(Clone)]
+struct Word {
+    text: String,
+    lowercase: String,
+    /// Character span in the annotations text
+    start: usize,
+    end: usize,
+    /// Position in the sequence of words
+    index: usize,
+}
+
+fn is_content_word(token: &nlprule::types::Token) -> bool {
+    let tags = token.word().tags();
+    tags.iter()
+        .any(|t| CONTENT_POS.iter().any(|p| t.pos().as_str().starts_with(p)))
+        && !tags
+            .iter()
+            .any(|t| AUXILIARIES.contains(&t.lemma().as_str()))
+}
+
+/// Stateful detector, to be fed the tokens of consecutive sentences.
+pub struct Repetitions<'a> {
+    config: &'a Config,
+    annotations: &'a api::Annotations,
+    /// Previous word, if only transparent tokens were seen since.
+    previous: Option<Word>,
+    /// Content words within the window.
+    recent: VecDeque<Word>,
+    n_words: usize,
+}
+impl<'a> Repetitions<'a> {
+    pub fn new(config: &'a Config, annotations: &'a api::Annotations) -> Self {
+        Self {
+            config,
+            annotations,
+            previous: None,
+            recent: Default::default(),
+            n_words: 0,
+        }
+    }
+    /// Process the next token, returning a match if it repeats a previous word.
+    pub fn push(&mut self, token: &nlprule::types::Token) -> Option<api::Match> {
+        self.push_word(
+            token.word().as_str(),
+            token.span().start().char,
+            token.span().end().char,
+            is_content_word(token),
+        )
+    }
+    /// Process the next word, given its character span in the annotations text.
+    fn push_word(
+        &mut self,
+        text: &str,
+        start: usize,
+        end: usize,
+        is_content: bool,
+    ) -> Option<api::Match> {
+        if text.is_empty() {
+            return None;
+        }
+        if !unidecode::unidecode(text).chars().all(char::is_alphabetic) {
+            if !TRANSPARENT.contains(&text) {
+                self.previous = None;
+            }
+            return None;
+        }
+        let current = Word {
+            text: text.into(),
+            lowercase: text.to_lowercase(),
+            start,
+            end,
+            index: self.n_words,
+        };
+        self.n_words += 1;
+
+        // Doubled words
+        if let Some(previous) = self.previous.replace(current.clone()).filter(|p| {
+            p.lowercase == current.lowercase && !self.config.is_allowed_double(&p.lowercase)
+        }) {
+            let (start, end) = self.annotations.translate_span(previous.start, current.end);
+            return Some(api::Match {
+                rule: api::Rule::duplication(),
+                message: "Possible typo: you repeated a word".into(),
+                replacements: vec![previous.text.into()],
+                offset: start,
+                length: end - start,
+                ..Default::default()
+            });
+        }
+
+        // Content words repeated nearby
+        if self.config.window == 0
+            || current.lowercase.chars().count() < self.config.min_length
+            || !is_content
+        {
+            return None;
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|w| w.index + self.config.window < current.index)
+        {
+            self.recent.pop_front();
+        }
+        let distance = self
+            .recent
+            .iter()
+            .find(|w| w.lowercase == current.lowercase)
+            .map(|w| current.index - w.index);
+        let (start, end) = self.annotations.translate_span(current.start, current.end);
+        let text = current.text.clone();
+        self.recent.push_back(current);
+        distance.map(|distance| api::Match {
+            rule: api::Rule::style(),
+            message: format!(
+                "The word '{}' was already used {} words before.",
+                text, distance
+            ),
+            offset: start,
+            length: end - start,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn allowed_doubles() {
+        let config = Config {
+            allow: vec!["Mahi".into()],
+            ..Default::default()
+        };
+        assert!(config.is_allowed_double("had"));
+        assert!(config.is_allowed_double("mahi"));
+        assert!(!config.is_allowed_double("the"));
+    }
+
+    /// Feed the words and punctuation of a text, all words being content words.
+    fn matches(config: &Config, text: &str) -> Vec<(usize, usize, String)> {
+        let annotations = api::Annotations {
+            annotation: vec![api::AnnotationElement::Text { text: text.into() }],
+        };
+        let mut repetitions = Repetitions::new(config, &annotations);
+        let tokens = regex::Regex::new(r"\w+|\S").unwrap();
+        tokens
+            .find_iter(text)
+            .filter_map(|t| {
+                let start = text[..t.start()].chars().count();
+                let end = start + t.as_str().chars().count();
+                repetitions.push_word(t.as_str(), start, end, true)
+            })
+            .map(|m| (m.offset, m.length, m.message))
+            .collect()
+    }
+
+    #[test]
+    fn doubles() {
+        let config = Config::default();
+        let doubled =
+            |offset, length| (offset, length, "Possible typo: you repeated a word".into());
+        assert_eq!(matches(&config, "The the garden"), [doubled(0, 7)]);
+        assert_eq!(matches(&config, "to “the” the park"), [doubled(4, 8)]);
+        assert_eq!(matches(&config, "the. The park"), []);
+        assert_eq!(matches(&config, "we had had enough"), []);
+    }
+
+    #[test]
+    fn window() {
+        let config = Config {
+            window: 3,
+            ..Default::default()
+        };
+        let repeated = |offset, distance| {
+            (
+                offset,
+                6,
+                format!(
+                    "The word 'Garden' was already used {} words before.",
+                    distance
+                ),
+            )
+        };
+        // The repeated word is 3 words away, at the boundary of the window
+        assert_eq!(
+            matches(&config, "garden, nice green Garden"),
+            [repeated(19, 3)]
+        );
+        assert_eq!(matches(&config, "garden, nice and green Garden"), []);
+        // Words shorter than the minimal length are ignored
+        assert_eq!(matches(&config, "tea and tea"), []);
+    }
+}
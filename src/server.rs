@@ -2,17 +2,18 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use axum::extract::{Extension, Form, Json};
+use axum::http::HeaderMap;
 use axum::response::IntoResponse;
 use clap::Parser;
 use log::*;
 use tokio::sync::RwLock;
 
 use ltapiserv_rs::api;
-use ltapiserv_rs::checkers::Checkers;
+use ltapiserv_rs::checkers::{self, Checkers};
+use ltapiserv_rs::{analysis, inclusive, repetition, stats, typography};
 
 fn dictionary() -> String {
-    dirs::data_dir()
-        .map(|d| d.join("ltapiserv-rs").join("dictionary.txt"))
+    checkers::default_dictionary()
         .and_then(|d| d.to_str().map(String::from))
         .unwrap_or_default()
 }
@@ -34,6 +35,18 @@ struct Flags {
     debug: bool,
     #[clap(long, default_value_t = 50_000)]
     max_query_size: usize,
+    /// Allow adding and deleting words of the custom dictionary through the API
+    #[clap(long)]
+    allow_dictionary_updates: bool,
+    /// Prefixes of the IDs of rules to disable
+    #[clap(long, value_delimiter = ',', default_values = checkers::DEFAULT_DISABLED_RULES)]
+    disable_rules: Vec<String>,
+    #[clap(flatten)]
+    repetition: repetition::Config,
+    #[clap(flatten)]
+    typography: typography::Config,
+    #[clap(flatten)]
+    inclusive: inclusive::Config,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -44,46 +57,87 @@ pub enum Error {
     MissingAnnotations(anyhow::Error),
     #[error("Query too large ({0} > {1})")]
     QueryTooLarge(usize, usize),
+    #[error("Invalid word {0:?} (should not be empty or contain whitespace)")]
+    InvalidWord(String),
+    #[error("Failed to update the dictionary: {0}")]
+    Dictionary(anyhow::Error),
+    #[error("Dictionary updates are disabled (see --allow-dictionary-updates)")]
+    DictionaryUpdatesDisabled,
+    #[error("Dictionary updates are not allowed from browsers")]
+    CrossOrigin,
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
         error!("{}", self.to_string());
-        (axum::http::StatusCode::BAD_REQUEST, self.to_string()).into_response()
+        let status = match self {
+            Error::Dictionary(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Error::DictionaryUpdatesDisabled | Error::CrossOrigin => {
+                axum::http::StatusCode::FORBIDDEN
+            }
+            _ => axum::http::StatusCode::BAD_REQUEST,
+        };
+        (status, self.to_string()).into_response()
     }
 }
 
 type CheckersExt = Extension<Arc<RwLock<Checkers>>>;
 
-/// Main endpoint.
-async fn check(
-    Extension(checkers): CheckersExt,
-    Extension(args): Extension<Arc<Flags>>,
-    Form(request): Form<api::Request>,
-) -> Result<Json<api::Response>, Error> {
-    let start = std::time::Instant::now();
-    info!("Received query");
-    debug!("Query {:#?}", request);
-    let checkers = checkers.read_owned().await;
+/// Validate the language and size of a request
+fn validate(request: &api::Request, checkers: &Checkers, args: &Flags) -> Result<(), Error> {
     if request.language() != checkers.language {
         return Err(Error::UnsupportedLanguage {
             request: request.language().to_string(),
             supports: checkers.language.to_string(),
         });
     }
-    let annotations = request.annotations().map_err(Error::MissingAnnotations)?;
-    let text_length = annotations.text_len();
-    if text_length > args.max_query_size {
-        return Err(Error::QueryTooLarge(text_length, args.max_query_size));
+    let length = request.input_len();
+    if length > args.max_query_size {
+        return Err(Error::QueryTooLarge(length, args.max_query_size));
     }
+    Ok(())
+}
 
-    // Process in a task
-    let resp: api::Response = tokio::task::spawn_blocking(move || api::Response {
-        matches: checkers.suggest(&annotations),
-        language: checkers.language.clone().into(),
+/// Validate a request, then convert it to annotations and run the checkers on them in a blocking
+/// task
+async fn process<T: Send + 'static>(
+    checkers: &Arc<RwLock<Checkers>>,
+    args: &Flags,
+    request: api::Request,
+    f: impl FnOnce(&Checkers, api::Annotations) -> T + Send + 'static,
+) -> Result<T, Error> {
+    let checkers = checkers.clone().read_owned().await;
+    validate(&request, &checkers, args)?;
+    tokio::task::spawn_blocking(move || {
+        let annotations = request.annotations().map_err(Error::MissingAnnotations)?;
+        Ok(f(&checkers, annotations))
     })
     .await
-    .unwrap();
+    .unwrap()
+}
+
+/// Main endpoint.
+async fn check(
+    Extension(checkers): CheckersExt,
+    Extension(args): Extension<Arc<Flags>>,
+    Form(request): Form<api::Request>,
+) -> Result<Json<api::Response>, Error> {
+    let start = std::time::Instant::now();
+    info!("Received query");
+    debug!("Query {:#?}", request);
+    let is_enabled = request.match_filter();
+    let (text_length, resp) = process(&checkers, &args, request, |checkers, annotations| {
+        let resp = api::Response {
+            matches: checkers
+                .suggest(&annotations)
+                .into_iter()
+                .filter(is_enabled)
+                .collect(),
+            language: checkers.language.clone().into(),
+        };
+        (annotations.text_len(), resp)
+    })
+    .await?;
 
     let elapsed_ms = start.elapsed().as_millis();
     info!(
@@ -96,6 +150,99 @@ async fn check(
     Ok(resp.into())
 }
 
+/// Readability statistics endpoint.
+async fn stats(
+    Extension(checkers): CheckersExt,
+    Extension(args): Extension<Arc<Flags>>,
+    Form(request): Form<api::Request>,
+) -> Result<Json<stats::Stats>, Error> {
+    info!("Received statistics query");
+    let stats = process(&checkers, &args, request, |checkers, annotations| {
+        checkers.stats(&annotations)
+    })
+    .await?;
+    Ok(stats.into())
+}
+
+/// Linguistic analysis endpoint.
+async fn analyze(
+    Extension(checkers): CheckersExt,
+    Extension(args): Extension<Arc<Flags>>,
+    Form(request): Form<api::Request>,
+) -> Result<Json<Vec<analysis::Sentence>>, Error> {
+    info!("Received analysis query");
+    let sentences = process(&checkers, &args, request, |checkers, annotations| {
+        checkers.analyze(&annotations)
+    })
+    .await?;
+    Ok(sentences.into())
+}
+
+/// Supported languages.
+async fn languages(Extension(checkers): CheckersExt) -> Json<Vec<api::LanguageInfo>> {
+    vec![(&checkers.read().await.language).into()].into()
+}
+
+/// Health check, for load balancers and clients.
+async fn healthcheck() -> &'static str {
+    "OK"
+}
+
+/// Words of the custom dictionary.
+async fn words(Extension(checkers): CheckersExt) -> Json<api::WordsResponse> {
+    api::WordsResponse {
+        words: checkers.read().await.dictionary_words(),
+    }
+    .into()
+}
+
+/// Validate a dictionary update. Requests from browsers (with an `Origin` header) are rejected,
+/// as other websites could otherwise submit forms to the server.
+fn validate_word(word: &str, args: &Flags, headers: &HeaderMap) -> Result<(), Error> {
+    if !args.allow_dictionary_updates {
+        return Err(Error::DictionaryUpdatesDisabled);
+    }
+    if headers.contains_key(axum::http::header::ORIGIN) {
+        return Err(Error::CrossOrigin);
+    }
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err(Error::InvalidWord(word.into()));
+    }
+    Ok(())
+}
+
+/// Add a word to the custom dictionary.
+async fn add_word(
+    Extension(checkers): CheckersExt,
+    Extension(args): Extension<Arc<Flags>>,
+    headers: HeaderMap,
+    Form(request): Form<api::WordRequest>,
+) -> Result<Json<api::AddWordResponse>, Error> {
+    validate_word(&request.word, &args, &headers)?;
+    let added = checkers
+        .write()
+        .await
+        .add_word(&args.dictionary, &request.word)
+        .map_err(Error::Dictionary)?;
+    Ok(api::AddWordResponse { added }.into())
+}
+
+/// Delete a word from the custom dictionary.
+async fn delete_word(
+    Extension(checkers): CheckersExt,
+    Extension(args): Extension<Arc<Flags>>,
+    headers: HeaderMap,
+    Form(request): Form<api::WordRequest>,
+) -> Result<Json<api::DeleteWordResponse>, Error> {
+    validate_word(&request.word, &args, &headers)?;
+    let deleted = checkers
+        .write()
+        .await
+        .delete_word(&args.dictionary, &request.word)
+        .map_err(Error::Dictionary)?;
+    Ok(api::DeleteWordResponse { deleted }.into())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     if let Err(err) = main_impl().await {
@@ -127,6 +274,10 @@ async fn main_impl() -> anyhow::Result<()> {
 
     // Add dictionary
     checkers.add_dictionary(Path::new(&args.dictionary))?;
+    checkers.repetition = args.repetition.clone();
+    checkers.typography = args.typography.clone();
+    checkers.inclusive = inclusive::Terms::new(&args.inclusive)?;
+    checkers.disabled_rules = args.disable_rules.clone();
 
     info!(
         "Done initializing {} checkers in {:?}",
@@ -165,7 +316,19 @@ async fn main_impl() -> anyhow::Result<()> {
     let app = axum::Router::new()
         .route("/check", axum::routing::post(check))
         .route("/v2/check", axum::routing::post(check))
+        .route("/v2/stats", axum::routing::post(stats))
+        .route("/v2/analyze", axum::routing::post(analyze))
+        .route("/v2/languages", axum::routing::get(languages))
+        .route("/v2/healthcheck", axum::routing::get(healthcheck))
+        .route("/v2/words", axum::routing::get(words))
         .layer(tower_http::cors::CorsLayer::new().allow_origin(tower_http::cors::Any))
+        // Without CORS, so that other websites cannot modify the dictionary from a browser
+        // (see also `validate_word`)
+        .merge(
+            axum::Router::new()
+                .route("/v2/words/add", axum::routing::post(add_word))
+                .route("/v2/words/delete", axum::routing::post(delete_word)),
+        )
         .layer(axum::extract::Extension(checkers))
         .layer(axum::extract::Extension(Arc::new(args)));
     info!("Serving on http://{}", addr);
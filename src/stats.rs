@@ -0,0 +1,153 @@
+/// Readability statistics, based on the [`nlprule`] tokenization.
+use serde::{Deserialize, Serialize};
+
+use crate::api;
+
+/// Number of longest sentences reported
+const LONGEST_SENTENCES: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Sentence {
+    pub offset: usize,
+    pub length: usize,
+    pub words: usize,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub words: usize,
+    pub sentences: usize,
+    pub paragraphs: usize,
+    pub syllables: usize,
+    pub flesch_reading_ease: f64,
+    pub flesch_kincaid_grade: f64,
+    pub average_sentence_length: f64,
+    pub longest_sentences: Vec<Sentence>,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Words:                   {}", self.words)?;
+        writeln!(f, "Sentences:               {}", self.sentences)?;
+        writeln!(f, "Paragraphs:              {}", self.paragraphs)?;
+        writeln!(
+            f,
+            "Average sentence length: {:.1} words",
+            self.average_sentence_length
+        )?;
+        writeln!(
+            f,
+            "Flesch reading ease:     {:.1}",
+            self.flesch_reading_ease
+        )?;
+        writeln!(
+            f,
+            "Flesch-Kincaid grade:    {:.1}",
+            self.flesch_kincaid_grade
+        )?;
+        writeln!(f, "Longest sentences:")?;
+        for s in &self.longest_sentences {
+            writeln!(f, "  - ({} words) {}", s.words, s.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimate the number of syllables in an English word, by counting groups of vowels.
+fn syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| "aeiouy".contains(c);
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    // Silent final e, e.g. in "make", but not in "table"
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+fn is_word(token: &str) -> bool {
+    token.chars().any(char::is_alphanumeric)
+}
+
+impl Stats {
+    /// Compute statistics on the sentences of the text contained in the annotations
+    pub fn compute<'t>(
+        sentences: impl Iterator<Item = nlprule::types::Sentence<'t>>,
+        annotations: &api::Annotations,
+    ) -> Self {
+        let mut stats = Stats {
+            paragraphs: annotations
+                .text()
+                .split("\n\n")
+                .filter(|p| !p.trim().is_empty())
+                .count(),
+            ..Default::default()
+        };
+        let mut sentences: Vec<Sentence> = sentences
+            .filter_map(|sentence| {
+                let words: Vec<_> = sentence
+                    .tokens()
+                    .iter()
+                    .map(|t| t.word().as_str())
+                    .filter(|w| is_word(w))
+                    .collect();
+                if words.is_empty() {
+                    return None;
+                }
+                stats.syllables += words.iter().map(|w| syllables(w)).sum::<usize>();
+                stats.words += words.len();
+                let span = sentence.span().char();
+                let (start, end) = annotations.translate_span(span.start, span.end);
+                Some(Sentence {
+                    offset: start,
+                    length: end - start,
+                    words: words.len(),
+                    text: sentence.text().trim().to_string(),
+                })
+            })
+            .collect();
+        stats.sentences = sentences.len();
+        if stats.words > 0 {
+            let words_per_sentence = stats.words as f64 / stats.sentences as f64;
+            let syllables_per_word = stats.syllables as f64 / stats.words as f64;
+            stats.average_sentence_length = words_per_sentence;
+            stats.flesch_reading_ease =
+                206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word;
+            stats.flesch_kincaid_grade =
+                0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59;
+        }
+        sentences.sort_by_key(|s| std::cmp::Reverse(s.words));
+        sentences.truncate(LONGEST_SENTENCES);
+        stats.longest_sentences = sentences;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn syllable_count() {
+        for (word, count) in [
+            ("a", 1),
+            ("the", 1),
+            ("make", 1),
+            ("table", 2),
+            ("readability", 5),
+            ("Rhythm", 1),
+        ] {
+            assert_eq!(syllables(word), count, "{}", word);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+/// Typography and whitespace checker: spaces around punctuation, quotes and dashes.
+///
+/// This works on the plain text rather than on tokens, as the tokenizer does not preserve
+/// whitespace.
+use crate::api;
+
+/// Sentence and clause punctuation
+const PUNCTUATION: &[char] = &[',', '.', ';', ':', '!', '?'];
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quotes {
+    /// Do not check quotes
+    Any,
+    /// Straight quotes and apostrophes: " '
+    Straight,
+    /// Curly quotes and apostrophes: “ ” ‘ ’
+    Curly,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dashes {
+    /// Do not check dashes
+    Any,
+    /// Unspaced em dashes: word—word
+    Em,
+    /// Spaced em dashes: word — word
+    SpacedEm,
+    /// Spaced en dashes: word – word
+    SpacedEn,
+}
+impl Dashes {
+    fn expected(&self) -> Option<&'static str> {
+        match self {
+            Dashes::Any => None,
+            Dashes::Em => Some("—"),
+            Dashes::SpacedEm => Some(" — "),
+            Dashes::SpacedEn => Some(" – "),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Config {
+    /// Enable the typography checks
+    #[clap(long = "typography")]
+    pub enable: bool,
+    /// Convention for quotes and apostrophes
+    #[clap(long, value_enum, default_value_t = Quotes::Any)]
+    pub quotes: Quotes,
+    /// Convention for dashes between words
+    #[clap(long, value_enum, default_value_t = Dashes::Any)]
+    pub dashes: Dashes,
+    /// Require en dashes in numeric ranges (e.g. 10–20)
+    #[clap(long)]
+    pub en_dash_ranges: bool,
+    /// Punctuation that must be preceded by a space (e.g. ";:!?" in French)
+    #[clap(long, default_value = "")]
+    pub space_before: String,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            quotes: Quotes::Any,
+            dashes: Dashes::Any,
+            en_dash_ranges: false,
+            space_before: "".into(),
+        }
+    }
+}
+
+/// Character-indexed text
+struct Text {
+    chars: Vec<char>,
+}
+impl Text {
+    fn get(&self, i: usize) -> Option<char> {
+        self.chars.get(i).copied()
+    }
+    /// Character before position `i`, if any
+    fn before(&self, i: usize) -> Option<char> {
+        i.checked_sub(1).and_then(|i| self.get(i))
+    }
+    fn is_space(c: Option<char>) -> bool {
+        c.is_some_and(|c| c == ' ' || c == '\t')
+    }
+    fn is_word(c: Option<char>) -> bool {
+        c.is_some_and(char::is_alphanumeric)
+    }
+    /// End of the run of characters satisfying `f` starting at `i`
+    fn run_end(&self, mut i: usize, f: impl Fn(char) -> bool) -> usize {
+        while self.get(i).is_some_and(&f) {
+            i += 1;
+        }
+        i
+    }
+}
+
+struct Checker<'a> {
+    config: &'a Config,
+    text: Text,
+    annotations: &'a api::Annotations,
+    matches: Vec<api::Match>,
+}
+impl Checker<'_> {
+    fn push(&mut self, rule: &str, message: &str, start: usize, end: usize, replacement: String) {
+        let (start, end) = self.annotations.translate_span(start, end);
+        self.matches.push(api::Match {
+            message: message.into(),
+            offset: start,
+            length: end - start,
+            replacements: vec![replacement.into()],
+            rule: api::Rule::typography(rule),
+            ..Default::default()
+        });
+    }
+
+    fn spaces(&mut self) {
+        let mut i = 0;
+        while i < self.text.chars.len() {
+            let c = self.text.chars[i];
+            let end = self.text.run_end(i, |c| c == ' ');
+            if end > i {
+                let before = self.text.before(i);
+                let after = self.text.get(end);
+                if after.is_some_and(|c| PUNCTUATION.contains(&c))
+                    && !self.config.space_before.contains(after.unwrap())
+                    && before.is_some_and(|c| !c.is_whitespace())
+                    // Skip e.g. " .NET" and ellipses
+                    && self.text.get(end + 1).is_none_or(char::is_whitespace)
+                {
+                    self.push(
+                        "TYPOGRAPHY_SPACE_BEFORE_PUNCTUATION",
+                        "Unnecessary space before punctuation.",
+                        i,
+                        end + 1,
+                        after.unwrap().to_string(),
+                    );
+                } else if end - i >= 2
+                    && before.is_some_and(|c| !c.is_whitespace())
+                    && after.is_some_and(|c| !c.is_whitespace())
+                {
+                    self.push(
+                        "TYPOGRAPHY_DOUBLE_SPACE",
+                        "Multiple consecutive spaces.",
+                        i,
+                        end,
+                        " ".into(),
+                    );
+                }
+                i = end;
+                continue;
+            }
+            if self.config.space_before.contains(c) && Text::is_word(self.text.before(i)) {
+                self.push(
+                    "TYPOGRAPHY_SPACE_BEFORE_PUNCTUATION",
+                    &format!("Missing space before '{}'.", c),
+                    i,
+                    i + 1,
+                    format!(" {}", c),
+                );
+            } else if PUNCTUATION.contains(&c) {
+                let before = self.text.before(i);
+                let after = self.text.get(i + 1);
+                let missing = match c {
+                    ',' | ';' => after.is_some_and(char::is_alphabetic),
+                    // Avoid abbreviations and file names, e.g. "e.g." or "file.txt"
+                    '.' | '!' | '?' => {
+                        before.is_some_and(char::is_lowercase)
+                            && self.text.before(i - 1).is_some_and(char::is_lowercase)
+                            && after.is_some_and(char::is_uppercase)
+                            && self.text.get(i + 2).is_some_and(char::is_lowercase)
+                    }
+                    // Colons are common in URLs and times
+                    _ => false,
+                };
+                if missing {
+                    self.push(
+                        "TYPOGRAPHY_SPACE_AFTER_PUNCTUATION",
+                        &format!("Missing space after '{}'.", c),
+                        i,
+                        i + 1,
+                        format!("{} ", c),
+                    );
+                }
+            }
+            i += 1;
+        }
+    }
+
+    fn quotes(&mut self) {
+        for i in 0..self.text.chars.len() {
+            let c = self.text.chars[i];
+            let replacement = match (self.config.quotes, c) {
+                (Quotes::Straight, '“' | '”') => '"',
+                (Quotes::Straight, '‘' | '’') => '\'',
+                (Quotes::Curly, '"' | '\'') => {
+                    let before = self.text.before(i);
+                    let opening = before.is_none_or(|c| c.is_whitespace() || "([{".contains(c));
+                    match (c, opening) {
+                        ('"', true) => '“',
+                        ('"', false) => '”',
+                        ('\'', true) if Text::is_word(self.text.get(i + 1)) => '‘',
+                        _ => '’',
+                    }
+                }
+                _ => continue,
+            };
+            self.push(
+                "TYPOGRAPHY_QUOTES",
+                &format!("Use '{}' rather than '{}'.", replacement, c),
+                i,
+                i + 1,
+                replacement.to_string(),
+            );
+        }
+    }
+
+    fn dashes(&mut self) {
+        let is_dash = |c: char| c == '-' || c == '–' || c == '—';
+        let mut i = 0;
+        while i < self.text.chars.len() {
+            if !is_dash(self.text.chars[i]) {
+                i += 1;
+                continue;
+            }
+            let dash_start = i;
+            let dash_end = self.text.run_end(dash_start, is_dash);
+            let dash: String = self.text.chars[dash_start..dash_end].iter().collect();
+            let mut start = dash_start;
+            while Text::is_space(self.text.before(start)) {
+                start -= 1;
+            }
+            let end = self.text.run_end(dash_end, |c| c == ' ' || c == '\t');
+            i = dash_end;
+            let before = self.text.before(start);
+            let after = self.text.get(end);
+            // Only consider dashes between words, which excludes e.g. list items
+            if before.is_none_or(char::is_whitespace) || after.is_none_or(char::is_whitespace) {
+                continue;
+            }
+            let spaced = start < dash_start || end > dash_end;
+            if !spaced && (dash == "-" || dash == "–") {
+                let range = before.is_some_and(|c| c.is_ascii_digit())
+                    && after.is_some_and(|c| c.is_ascii_digit());
+                // Skip e.g. dates such as 2024-01-01
+                let mut number_start = start;
+                while self
+                    .text
+                    .before(number_start)
+                    .is_some_and(|c| c.is_ascii_digit())
+                {
+                    number_start -= 1;
+                }
+                let number_end = self.text.run_end(end, |c| c.is_ascii_digit());
+                let isolated = self.text.before(number_start).is_none_or(|c| c != '-')
+                    && self.text.get(number_end).is_none_or(|c| c != '-');
+                if dash == "-" && range && isolated && self.config.en_dash_ranges {
+                    self.push(
+                        "TYPOGRAPHY_DASHES",
+                        "Use an en dash in ranges.",
+                        start,
+                        end,
+                        "–".into(),
+                    );
+                }
+                // Hyphenated words and en dashes in compounds
+                continue;
+            }
+            let Some(expected) = self.config.dashes.expected() else {
+                continue;
+            };
+            let found: String = self.text.chars[start..end].iter().collect();
+            if found != expected {
+                self.push(
+                    "TYPOGRAPHY_DASHES",
+                    &format!("Use '{}' as a dash.", expected),
+                    start,
+                    end,
+                    expected.into(),
+                );
+            }
+        }
+    }
+}
+
+/// Check the typography of the text contained in the annotations
+pub fn check(config: &Config, annotations: &api::Annotations) -> Vec<api::Match> {
+    if !config.enable {
+        return vec![];
+    }
+    let mut checker = Checker {
+        config,
+        text: Text {
+            chars: annotations.text().chars().collect(),
+        },
+        annotations,
+        matches: vec![],
+    };
+    checker.spaces();
+    checker.quotes();
+    checker.dashes();
+    let mut matches = checker.matches;
+    matches.sort_by_key(|m| m.offset);
+    matches
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_text(config: &Config, text: &str) -> Vec<(String, String)> {
+        let annotations = api::Request::new(text.into(), "en-US")
+            .annotations()
+            .unwrap();
+        let config = Config {
+            enable: true,
+            ..config.clone()
+        };
+        check(&config, &annotations)
+            .into_iter()
+            .map(|m| {
+                (
+                    text.chars().skip(m.offset).take(m.length).collect(),
+                    m.replacements[0].value.clone(),
+                )
+            })
+            .collect()
+    }
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn spaces() {
+        // Disabled by default
+        let annotations = api::Request::new("Hello  world".into(), "en-US")
+            .annotations()
+            .unwrap();
+        assert!(check(&Config::default(), &annotations).is_empty());
+
+        let config = Config::default();
+        assert_eq!(
+            check_text(
+                &config,
+                "Hello  world , fine.Then,done e.g. file.txt\n  - item"
+            ),
+            pairs(&[("  ", " "), (" ,", ","), (".", ". "), (",", ", ")])
+        );
+        let config = Config {
+            space_before: ";:!?".into(),
+            ..Default::default()
+        };
+        assert_eq!(check_text(&config, "Bonjour! Oui ?"), pairs(&[("!", " !")]));
+    }
+
+    #[test]
+    fn quotes() {
+        let config = Config {
+            quotes: Quotes::Curly,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_text(&config, r#"He said "don't"."#),
+            pairs(&[("\"", "“"), ("'", "’"), ("\"", "”")])
+        );
+        let config = Config {
+            quotes: Quotes::Straight,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_text(&config, "“It’s”"),
+            pairs(&[("“", "\""), ("’", "'"), ("”", "\"")])
+        );
+    }
+
+    #[test]
+    fn dashes() {
+        let config = Config {
+            dashes: Dashes::SpacedEn,
+            en_dash_ranges: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_text(
+                &config,
+                "well-known - or not—pages 10-20, on 2024-01-01 – fine\n- list"
+            ),
+            pairs(&[(" - ", " – "), ("—", " – "), ("-", "–")])
+        );
+        let config = Config {
+            dashes: Dashes::Em,
+            ..Default::default()
+        };
+        assert_eq!(check_text(&config, "yes -- no"), pairs(&[(" -- ", "—")]));
+    }
+}
@@ -1,9 +1,12 @@
 static DATA: include_dir::Dir<'_> = include_dir::include_dir!("tests/data");
 
+fn checkers() -> anyhow::Result<ltapiserv_rs::checkers::Checkers> {
+    ltapiserv_rs::checkers::Checkers::from_archive_bytes(include_bytes!("../en_US.tar.gz"))
+}
+
 #[test]
 fn main() -> anyhow::Result<()> {
-    let checkers =
-        ltapiserv_rs::checkers::Checkers::from_archive_bytes(include_bytes!("../en_US.tar.gz"))?;
+    let checkers = checkers()?;
 
     for data in DATA.find("*.txt")?.filter_map(|d| d.as_file()) {
         let request =
@@ -26,3 +29,63 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Offsets and lengths of the matches of a rule
+fn matches(
+    checkers: &ltapiserv_rs::checkers::Checkers,
+    request: ltapiserv_rs::api::Request,
+    rule: &ltapiserv_rs::api::Rule,
+) -> Vec<(usize, usize)> {
+    checkers
+        .suggest(&request.annotations().unwrap())
+        .iter()
+        .filter(|m| m.rule == *rule)
+        .map(|m| (m.offset, m.length))
+        .collect()
+}
+
+#[test]
+fn repetitions() -> anyhow::Result<()> {
+    let checkers = checkers()?;
+    let text = "The the garden was quiet.\nWe walked to the\nthe park.\n\
+        She said “yes” “yes” to him.\nBy noon we had had enough.";
+    let request = ltapiserv_rs::api::Request::new(text.into(), "en-US");
+    let duplication = ltapiserv_rs::api::Rule::duplication();
+    pretty_assertions::assert_eq!(
+        matches(&checkers, request, &duplication),
+        [(0, 7), (39, 7), (63, 9)]
+    );
+    // Across markup: "We walked to the **the** park today."
+    let data = serde_json::json!({"annotation": [
+        {"text": "We walked to the "},
+        {"markup": "**"},
+        {"text": "the"},
+        {"markup": "**"},
+        {"text": " park today."},
+    ]});
+    let request: ltapiserv_rs::api::Request = serde_json::from_value(serde_json::json!({
+        "data": data.to_string(),
+        "language": "en-US",
+    }))?;
+    pretty_assertions::assert_eq!(matches(&checkers, request, &duplication), [(13, 9)]);
+    Ok(())
+}
+
+#[test]
+fn repetition_window() -> anyhow::Result<()> {
+    let mut checkers = checkers()?;
+    checkers.repetition.window = 10;
+    let text = "The garden was quiet, and the garden was green.";
+    let request = ltapiserv_rs::api::Request::new(text.into(), "en-US");
+    let suggestions = checkers.suggest(&request.annotations().unwrap());
+    let repeated: Vec<_> = suggestions
+        .iter()
+        .filter(|m| m.rule == ltapiserv_rs::api::Rule::style())
+        .map(|m| (m.offset, m.length, m.message.as_str()))
+        .collect();
+    pretty_assertions::assert_eq!(
+        repeated,
+        [(30, 6, "The word 'garden' was already used 5 words before.")]
+    );
+    Ok(())
+}